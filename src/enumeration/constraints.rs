@@ -0,0 +1,133 @@
+//! Restrictions on the marks and distances that an enumerated ruler may use.
+//!
+//! Constraints are a [`Pruner`] checked against every _prefix_ of a [`RulerState`](super::state::RulerState), so
+//! the constrained enumerators can skip entire subtrees instead of filtering completed rulers.
+
+// `#[new]` constructors expand to non-local impls with pyo3 0.20
+#![cfg_attr(feature = "python", allow(non_local_definitions))]
+
+use std::collections::HashSet;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+use crate::{GInt, Ruler};
+use super::pruners::{Pruner, PartialRuler, Verdict};
+
+/// A set of constraints that every enumerated ruler must satisfy.
+///
+/// * `forbidden_marks` - Marks that must not be used
+/// * `required_marks` - Marks that must be present
+/// * `forbidden_distances` - Distances that must not appear between any two marks
+/// * `min_gap` - Minimum distance between neighbouring marks
+/// * `max_gap` - Maximum distance between neighbouring marks
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "python", pyclass(get_all))]
+pub struct RulerConstraints {
+    pub forbidden_marks: HashSet<GInt>,
+    pub required_marks: HashSet<GInt>,
+    pub forbidden_distances: HashSet<GInt>,
+    pub min_gap: Option<GInt>,
+    pub max_gap: Option<GInt>,
+}
+
+impl RulerConstraints {
+
+    pub fn new(
+        forbidden_marks: Vec<GInt>,
+        required_marks: Vec<GInt>,
+        forbidden_distances: Vec<GInt>,
+        min_gap: Option<GInt>,
+        max_gap: Option<GInt>,
+    ) -> Self {
+        RulerConstraints {
+            forbidden_marks: forbidden_marks.into_iter().collect(),
+            required_marks: required_marks.into_iter().collect(),
+            forbidden_distances: forbidden_distances.into_iter().collect(),
+            min_gap,
+            max_gap,
+        }
+    }
+}
+
+#[cfg_attr(feature = "python", pymethods)]
+impl RulerConstraints {
+
+    #[cfg(feature = "python")]
+    #[new]
+    #[pyo3(signature = (forbidden_marks=Vec::new(), required_marks=Vec::new(), forbidden_distances=Vec::new(), min_gap=None, max_gap=None))]
+    fn py_new(
+        forbidden_marks: Vec<GInt>,
+        required_marks: Vec<GInt>,
+        forbidden_distances: Vec<GInt>,
+        min_gap: Option<GInt>,
+        max_gap: Option<GInt>,
+    ) -> Self {
+        RulerConstraints::new(forbidden_marks, required_marks, forbidden_distances, min_gap, max_gap)
+    }
+
+    #[cfg(feature = "python")]
+    fn __repr__(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    /// Check if a complete ruler satisfies every constraint. The golomb property is _not_ checked.
+    pub fn admits(&self, ruler: &Ruler) -> bool {
+
+        let mut marks: Vec<GInt> = vec![0];
+        marks.extend(&ruler.marks);
+
+        if marks.iter().any(|m| self.forbidden_marks.contains(m)) {
+            return false
+        }
+
+        if self.required_marks.iter().any(|m| !marks.contains(m)) {
+            return false
+        }
+
+        for w in marks.windows(2) {
+            let gap = w[1] - w[0];
+            if self.min_gap.is_some_and(|g| gap < g) || self.max_gap.is_some_and(|g| gap > g) {
+                return false
+            }
+        }
+
+        for (idx, lhs) in marks.iter().enumerate() {
+            for rhs in &marks[idx + 1..] {
+                if self.forbidden_distances.contains(&(rhs - lhs)) {
+                    return false
+                }
+            }
+        }
+
+        true
+    }
+}
+
+/// Constraints prune every subtree as soon as its prefix violates them. The golomb property and the order are _not_ checked.
+impl Pruner for RulerConstraints {
+    fn check(&self, partial: &PartialRuler) -> Verdict {
+
+        let length = partial.length as GInt;
+        let depth = partial.depth as GInt;
+        let last = partial.last_mark();
+
+        // Marks placed so far, without the final mark
+        let placed = if partial.is_leaf { &partial.marks[..partial.marks.len() - 1] } else { partial.marks };
+        let gaps = || placed.windows(2).map(|w| w[1] - w[0]);
+
+        // The marks 0 and `length` are always present, and every distance between placed marks and `length` is counted
+        let violated = self.forbidden_marks.contains(&length)
+            || placed.iter().any(|m| self.forbidden_marks.contains(m))
+            || self.forbidden_distances.iter().any(|d| *d > 0 && partial.distances.get(*d as usize).is_some_and(|c| *c > 0))
+            || self.required_marks.iter().any(|m| *m < 0 || *m > length || (*m <= depth && *m != length && placed.binary_search(m).is_err()))
+            // Any mark placed after the last one can only shrink the final gap further
+            || self.min_gap.is_some_and(|g| length - last < g || gaps().any(|gap| gap < g))
+            // At an inner node, the next mark is at least one position past `depth`
+            || self.max_gap.is_some_and(|g| gaps().any(|gap| gap > g) || match partial.is_leaf {
+                true => length - last > g,
+                false => depth - last >= g,
+            });
+
+        if violated { Verdict::PruneSubtree } else { Verdict::Keep }
+    }
+}
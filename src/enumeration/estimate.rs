@@ -0,0 +1,248 @@
+//! Estimate the size of a pruned search tree before running an enumeration.
+//!
+//! We follow Knuth's random probes: walk down the tree from the root, picking a random child that survives pruning
+//! at every node. Every visited node stands for as many nodes as there were choices along the path leading to it,
+//! which makes the weighted count of a probe an unbiased estimate of the size of the tree. Averaging many probes
+//! gives an estimate with a confidence interval.
+//!
+//! Runtimes are projected by running the actual enumeration for a short while and measuring how fast it visits nodes.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+use crate::GInt;
+use crate::error::{Result, check_order};
+#[cfg(feature = "python")]
+use crate::error::OgrError;
+use crate::rulers::SplitMix64;
+use super::iterators::PrunerIterator;
+use super::pruners::{Pruner, Verdict, PartialRuler, MarkCount, PruneScratch};
+
+/// Estimated size of a search tree, and how long it would take to visit
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "python", pyclass)]
+pub struct TreeEstimate {
+    /// Estimated number of nodes visited
+    pub nodes: f64,
+    /// Lower end of the 95% confidence interval of `nodes`
+    pub low: f64,
+    /// Upper end of the 95% confidence interval of `nodes`
+    pub high: f64,
+    /// Number of probes per length
+    pub probes: usize,
+    /// Number of nodes visited while timing the enumeration
+    pub sampled_nodes: usize,
+    /// Whether the timed sample visited the whole tree, in which case `sampled_nodes` is exact
+    pub complete: bool,
+    /// Number of nodes visited per second while timing the enumeration
+    pub nodes_per_second: f64,
+    /// Projected time to visit `nodes` nodes
+    pub runtime: Duration,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl TreeEstimate {
+
+    #[getter]
+    fn nodes(&self) -> f64 {
+        self.nodes
+    }
+
+    #[getter]
+    fn low(&self) -> f64 {
+        self.low
+    }
+
+    #[getter]
+    fn high(&self) -> f64 {
+        self.high
+    }
+
+    #[getter]
+    fn probes(&self) -> usize {
+        self.probes
+    }
+
+    #[getter]
+    fn sampled_nodes(&self) -> usize {
+        self.sampled_nodes
+    }
+
+    #[getter]
+    fn complete(&self) -> bool {
+        self.complete
+    }
+
+    #[getter]
+    fn nodes_per_second(&self) -> f64 {
+        self.nodes_per_second
+    }
+
+    /// Projected runtime, in seconds
+    #[getter(runtime)]
+    fn py_runtime(&self) -> f64 {
+        self.runtime.as_secs_f64()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("TreeEstimate(nodes={:.0}, low={:.0}, high={:.0}, runtime={:?})", self.nodes, self.low, self.high, self.runtime)
+    }
+}
+
+/// Walk a random path down the tree of rulers of length `length`, returning its estimate of the number of nodes
+fn probe<P: Pruner + ?Sized>(length: usize, pruner: &P, scratch: &mut PruneScratch, rng: &mut SplitMix64) -> f64 {
+
+    scratch.reset(length, pruner);
+    if scratch.check(pruner, 0) == Verdict::PruneSubtree {
+        return 1.0
+    }
+
+    let mut nodes = 1.0;
+    let mut weight = 1.0;
+
+    for depth in 1..length {
+
+        // Both children are visited, but only those that survive pruning have children of their own
+        let mut live = vec![];
+        for mark in [false, true] {
+            if mark {
+                scratch.push(depth as GInt);
+            }
+            if scratch.check(pruner, depth) != Verdict::PruneSubtree {
+                live.push(mark);
+            }
+            scratch.truncate(depth - 1);
+        }
+
+        nodes += 2.0 * weight;
+        if live.is_empty() {
+            break
+        }
+
+        weight *= live.len() as f64;
+        if live[rng.below(live.len())] {
+            scratch.push(depth as GInt);
+        }
+    }
+
+    nodes
+}
+
+/// Estimate the number of nodes in the tree of rulers of length `length` pruned by `pruner`, with `probes` probes.
+///
+/// Returns the mean of the probes and the variance of that mean.
+fn estimate_length<P: Pruner + ?Sized>(length: usize, pruner: &P, probes: usize, rng: &mut SplitMix64) -> (f64, f64) {
+
+    let mut scratch = PruneScratch::default();
+    let samples: Vec<f64> = (0..probes).map(|_| probe(length, pruner, &mut scratch, rng)).collect();
+
+    let n = samples.len() as f64;
+    let mean = samples.iter().sum::<f64>() / n;
+    let variance = match samples.len() {
+        0 | 1 => 0.0,
+        _ => samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (n - 1.0),
+    };
+
+    (mean, variance / n)
+}
+
+/// Count the nodes visited by a pruner, and prune everything once a deadline has passed
+#[derive(Debug)]
+struct Timed<P> {
+    pruner: P,
+    nodes: AtomicUsize,
+    deadline: Instant,
+    expired: AtomicBool,
+}
+
+impl<P: Pruner> Pruner for Timed<P> {
+    fn check(&self, partial: &PartialRuler) -> Verdict {
+
+        if self.expired.load(Ordering::Relaxed) {
+            return Verdict::PruneSubtree
+        }
+
+        if !partial.is_leaf {
+            // Reading the clock at every node would slow down what we measure
+            let nodes = self.nodes.fetch_add(1, Ordering::Relaxed) + 1;
+            if nodes.is_multiple_of(1024) && Instant::now() >= self.deadline {
+                self.expired.store(true, Ordering::Relaxed);
+                return Verdict::PruneSubtree
+            }
+        }
+
+        self.pruner.check(partial)
+    }
+
+    fn needs_distances(&self) -> bool {
+        self.pruner.needs_distances()
+    }
+}
+
+/// Estimate the search tree of [`enumerate_golomb_rulers_pruned(order, max_length)`](super::enumerate_golomb_rulers_pruned)
+/// with `probes` random probes per length, reproducibly for a given `seed`.
+///
+/// The projected runtime is calibrated by running the enumeration for at most `calibration`, starting from the longest
+/// rulers, whose trees dominate the total.
+pub fn estimate_golomb_rulers_pruned(order: usize, max_length: usize, probes: usize, seed: u64, calibration: Duration) -> Result<TreeEstimate> {
+
+    check_order(order)?;
+
+    let mut rng = SplitMix64::new(seed);
+    let probes = usize::max(probes, 1);
+
+    // Lengths with no ruler of this order are pruned at the root, and traversals only start from length 2
+    let lengths = || usize::max(2, order.saturating_sub(1))..=max_length;
+
+    let (nodes, variance) = lengths().map(|length| estimate_length(length, &MarkCount(order), probes, &mut rng))
+        .fold((0.0, 0.0), |(n, v), (mean, var)| (n + mean, v + var));
+
+    let timed = Arc::new(Timed {
+        pruner: MarkCount(order),
+        nodes: AtomicUsize::new(0),
+        deadline: Instant::now() + calibration,
+        expired: AtomicBool::new(false),
+    });
+
+    let start = Instant::now();
+    for length in lengths().rev() {
+        PrunerIterator::new_unchecked(length, timed.clone()).for_each(drop);
+        if timed.expired.load(Ordering::Relaxed) {
+            break
+        }
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let sampled_nodes = timed.nodes.load(Ordering::Relaxed);
+    let nodes_per_second = if elapsed > 0.0 { sampled_nodes as f64 / elapsed } else { f64::INFINITY };
+    let margin = 1.96 * variance.sqrt();
+
+    Ok(TreeEstimate {
+        nodes,
+        low: f64::max(nodes - margin, 0.0),
+        high: nodes + margin,
+        probes,
+        sampled_nodes,
+        complete: !timed.expired.load(Ordering::Relaxed),
+        nodes_per_second,
+        runtime: Duration::try_from_secs_f64(nodes / nodes_per_second).unwrap_or(Duration::MAX),
+    })
+}
+
+/// Estimate how many nodes `enumerate_golomb_rulers_pruned(order, max_length)` visits, and how long it takes.
+///
+/// Uses `probes` random root-to-leaf probes per length, reproducibly for a given `seed`, and calibrates
+/// the projected runtime by enumerating for at most `calibration` seconds.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "estimate_golomb_rulers_pruned", signature = (order, max_length, probes=1000, seed=0, calibration=0.1))]
+pub fn py_estimate_golomb_rulers_pruned(order: usize, max_length: usize, probes: usize, seed: u64, calibration: f64) -> Result<TreeEstimate> {
+    let calibration = Duration::try_from_secs_f64(calibration.max(0.0))
+        .map_err(|_| OgrError::Overflow(format!("A calibration of {} seconds", calibration)))?;
+    estimate_golomb_rulers_pruned(order, max_length, probes, seed, calibration)
+}
@@ -1,105 +1,431 @@
 //! Implementations of different enumeration strategies to explore the Golomb Ruler space
 
-use crate::GolombRuler;
-use super::state::RulerState;
+// `#[new]` constructors expand to non-local impls with pyo3 0.20
+#![cfg_attr(feature = "python", allow(non_local_definitions))]
 
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
 
-/// Iterator over all possible rulers with length `length`.
-pub(super) struct RulerIterator {
+use crate::{GInt, GolombRuler, Ruler};
+use crate::error::{OgrError, Result, check_order, check_tree_length};
+use super::state::{RulerState, seek_state, state_from_id};
+use super::constraints::RulerConstraints;
+use super::pruners::*;
+
+/// Iterators that can jump directly to the ruler with a given id.
+pub trait Seek {
+    /// Position this iterator so that the next ruler it yields is the ruler with id `id`,
+    /// or the first ruler following it in this iterator's order.
+    ///
+    /// Iterators over a single length traverse their rulers in a different order than their ids.
+    /// Seeking to a shorter ruler restarts the iteration and seeking to a longer ruler ends it.
+    fn seek(&mut self, id: usize);
+}
+
+
+/// Iterator over the rulers of length `length` accepted by a [`Pruner`].
+///
+/// Every enumeration strategy of this module is a `PrunerIterator` with a built-in pruner.
+#[derive(Debug)]
+pub struct PrunerIterator<P: Pruner> {
     state: Vec<bool>,
-    length: usize
+    length: usize,
+    pruner: P,
+    scratch: PruneScratch,
 }
 
-/// Iterator that prunes the tree when the order has been hit or the golomb property is
+/// Pruner of the golomb rulers with a given order
+type GolombPruner = And<MarkCount, GolombProperty>;
+
+/// Iterator over the golomb rulers of a given order and length whose first marks are a fixed prefix.
+///
+/// The search starts at the left-most state of the subtree implied by the prefix and ends as soon as it leaves it.
 #[derive(Debug)]
-pub(super) struct GolombRulerPrunedIterator {
+#[cfg_attr(feature = "python", pyclass)]
+pub struct GolombRulerPrefixIterator {
     state: Vec<bool>,
-    order: usize,
-    length: usize
+    length: usize,
+    /// The first values of every state in our subtree
+    prefix: Vec<bool>,
+    /// The state preceding the first state of our subtree
+    start: Vec<bool>,
+    pruner: GolombPruner,
+    scratch: PruneScratch,
 }
 
-/// Iterator that initially only checks the golomb property up until a certain depth
+/// Which rulers a [`RulerWalker`] stops at.
+#[derive(Clone, Debug)]
+pub enum WalkMode {
+    /// Every ruler
+    All,
+    /// Every ruler with the given order
+    Order(usize),
+    /// Every golomb ruler with the given order
+    Golomb(usize),
+    /// Every golomb ruler with the given order that satisfies a set of constraints
+    Constrained(usize, Box<RulerConstraints>),
+}
+
+impl WalkMode {
+    /// The pruner selecting the rulers of this mode
+    fn pruner(self) -> Box<dyn Pruner> {
+        match self {
+            WalkMode::All => Box::new(NoPruning),
+            WalkMode::Order(order) => Box::new(MarkCount(order)),
+            WalkMode::Golomb(order) => Box::new(MarkCount(order).and(GolombProperty)),
+            WalkMode::Constrained(order, constraints) => Box::new(MarkCount(order).and(GolombProperty).and(*constraints)),
+        }
+    }
+}
+
+/// Allocation-free traversal of the rulers with a single length.
+///
+/// The iterators of this module allocate a new [`Ruler`] for every item. A walker instead mutates
+/// a single state in place and lends out a view of the current marks, including 0 and the length, which is
+/// valid until the next step. Use [`RulerWalker::next_marks`] as a lending iterator or
+/// [`RulerWalker::visit`] to run a closure on every ruler.
 #[derive(Debug)]
-pub(super) struct GolombRulerDepthIterator {
+pub struct RulerWalker {
     state: Vec<bool>,
-    order: usize,
+    marks: Vec<GInt>,
     length: usize,
-    depth: usize,
+    pruner: Box<dyn Pruner>,
+    scratch: PruneScratch,
+}
+
+/// Iterator over every ruler with an id in `[id, end_id)`, in order of their ids.
+///
+/// When `order` is set, ids of rulers with a different order are skipped without being visited.
+#[derive(Debug)]
+#[cfg_attr(feature = "python", pyclass)]
+pub struct RulerIdIterator {
+    state: Vec<bool>,
+    id: usize,
+    end_id: usize,
+    order: Option<usize>,
 }
 
 /* -------------------------------------------------------------------------- */
 /*                             New Implementations                            */
 /* -------------------------------------------------------------------------- */
-impl GolombRulerPrunedIterator {
-    pub(super) fn new(order: usize, length: usize) -> Self {
+impl<P: Pruner> PrunerIterator<P> {
+
+    /// Create an iterator over the rulers of length `length` accepted by `pruner`.
+    ///
+    /// Fails for lengths below 2, as the rulers `[0]` and `[0, 1]` have no state to traverse.
+    pub fn with_pruner(length: usize, pruner: P) -> Result<Self> {
+        check_tree_length(length)?;
+        Ok(PrunerIterator::new_unchecked(length, pruner))
+    }
+
+    /// Create an iterator over the rulers of length `length`, which the caller knows to be at least 2
+    pub(crate) fn new_unchecked(length: usize, pruner: P) -> Self {
+        debug_assert!(length >= 2, "ruler states start at length 2, got {}", length);
         // The initial state should return our starting point on next()
         // this pre-state accomplishes that
         let pre_state = vec![false; length - 2];
-        GolombRulerPrunedIterator {
+        PrunerIterator {
             state: pre_state,
-            order,
             length,
+            pruner,
+            scratch: PruneScratch::default(),
         }
     }
 }
 
-impl RulerIterator {
-    pub(super) fn new(length: usize) -> Self {
-        // The initial state should return our starting point on next()
-        // this pre-state accomplishes that
-        let pre_state = vec![false; length - 2];
-        RulerIterator {
-            state: pre_state,
+impl GolombRulerPrefixIterator {
+
+    /// Create an iterator over golomb rulers of order `order` and length `length` starting with the marks `prefix`.
+    ///
+    /// The prefix must start at 0 and have the golomb property itself. The length must be at least 2.
+    pub fn new(prefix: &[GInt], order: usize, length: usize) -> Result<Self> {
+
+        check_order(order)?;
+        check_tree_length(length)?;
+        validate_prefix(prefix)?;
+
+        let last = prefix[prefix.len() - 1];
+        let (prefix_state, start) = if last as usize > length {
+            // No ruler this short can start with our prefix, so we begin at the final state
+            (vec![], vec![true; length - 1])
+        } else {
+            // When the prefix reaches `length`, its final mark is implied and the whole state is fixed
+            let depth = usize::min(last as usize, length - 1);
+            let mut prefix_state = vec![false; depth];
+            prefix.iter().filter(|m| **m > 0 && (**m as usize) <= depth).for_each(|m| prefix_state[*m as usize - 1] = true);
+
+            // Left-most leaf of our subtree
+            let mut first = prefix_state.clone();
+            first.resize(length - 1, false);
+            let start = first.previous().unwrap_or(vec![false; length - 2]);
+
+            (prefix_state, start)
+        };
+
+        Ok(GolombRulerPrefixIterator {
+            state: start.clone(),
             length,
-        }
+            prefix: prefix_state,
+            start,
+            pruner: MarkCount(order).and(GolombProperty),
+            scratch: PruneScratch::default(),
+        })
     }
 }
 
+#[cfg(feature = "python")]
+#[pymethods]
+impl GolombRulerPrefixIterator {
 
+    #[new]
+    fn py_new(prefix: Vec<GInt>, order: usize, length: usize) -> Result<Self> {
+        GolombRulerPrefixIterator::new(&prefix, order, length)
+    }
 
-impl GolombRulerDepthIterator {
-    pub(super) fn new(order: usize, length: usize, depth: usize) -> Self {
-        let pre_state = vec![false; length - 2];
-        GolombRulerDepthIterator {
-            state: pre_state,
-            order,
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<'_, Self>) -> Option<GolombRuler> {
+        slf.next()
+    }
+}
+
+/// Verify that `prefix` starts at 0, is strictly increasing and has the golomb property.
+pub(super) fn validate_prefix(prefix: &[GInt]) -> Result<()> {
+
+    let invalid = |reason: &str| Err(OgrError::InvalidMarks { marks: prefix.to_vec(), reason: format!("invalid prefix, {}", reason) });
+
+    if prefix.first() != Some(&0) {
+        return invalid("the first mark must be 0")
+    }
+
+    if prefix.windows(2).any(|w| w[0] >= w[1]) {
+        return invalid("marks must be strictly increasing")
+    }
+
+    if !Ruler::new(&prefix[1..]).is_golomb_ruler() {
+        return invalid("the prefix does not have the golomb property")
+    }
+
+    Ok(())
+}
+
+impl RulerWalker {
+
+    /// Create a walker over the rulers of length `length` selected by `mode`. The length must be at least 2.
+    pub fn new(length: usize, mode: WalkMode) -> Result<Self> {
+        RulerWalker::with_pruner(length, mode.pruner())
+    }
+
+    /// Create a walker over the rulers of length `length` accepted by `pruner`. The length must be at least 2.
+    pub fn with_pruner(length: usize, pruner: impl Pruner + 'static) -> Result<Self> {
+        check_tree_length(length)?;
+        Ok(RulerWalker {
+            state: vec![false; length - 2],
+            marks: Vec::with_capacity(length + 1),
             length,
-            depth,
+            pruner: Box::new(pruner),
+            scratch: PruneScratch::default(),
+        })
+    }
+
+    /// Step to the next ruler and return its marks, or `None` once the traversal is over.
+    pub fn next_marks(&mut self) -> Option<&[GInt]> {
+
+        let found = self.state.advance_with(self.length, &self.pruner, &mut self.scratch);
+
+        if !found {
+            return None
+        }
+
+        self.marks.clear();
+        self.marks.push(0);
+        self.marks.extend(self.state.iter().enumerate().filter(|(_, b)| **b).map(|(idx, _)| idx as GInt + 1));
+        self.marks.push(self.length as GInt);
+
+        Some(&self.marks)
+    }
+
+    /// Call `visit` with the marks of every remaining ruler.
+    pub fn visit(&mut self, mut visit: impl FnMut(&[GInt])) {
+        while let Some(marks) = self.next_marks() {
+            visit(marks);
+        }
+    }
+
+    /// The current state of our traversal
+    pub fn state(&self) -> &[bool] {
+        &self.state
+    }
+}
+
+impl RulerIdIterator {
+
+    pub fn new(start_id: usize, end_id: usize, order: Option<usize>) -> Self {
+        let mut iter = RulerIdIterator {
+            state: vec![],
+            id: 0,
+            end_id,
+            order,
+        };
+        iter.seek(start_id);
+        iter
+    }
+}
+
+#[cfg_attr(feature = "python", pymethods)]
+impl RulerIdIterator {
+
+    /// Position this iterator so that the next ruler it yields has an id of at least `id`.
+    pub fn seek(&mut self, id: usize) {
+        self.id = id;
+        self.state = if id == 0 { vec![] } else { state_from_id(id) };
+    }
+
+    #[cfg(feature = "python")]
+    #[new]
+    #[pyo3(signature = (start_id, end_id, order=None))]
+    fn py_new(start_id: usize, end_id: usize, order: Option<usize>) -> Self {
+        RulerIdIterator::new(start_id, end_id, order)
+    }
+
+    #[cfg(feature = "python")]
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    #[cfg(feature = "python")]
+    fn __next__(mut slf: PyRefMut<'_, Self>) -> Option<Ruler> {
+        slf.next()
+    }
+}
+
+/// Return the smallest id greater than or equal to `id` whose ruler has order `order`.
+fn first_id_with_order(id: usize, order: usize) -> Option<usize> {
+
+    // Only the ruler [0] has order 1
+    if order < 2 {
+        return if id == 0 && order == 1 { Some(0) } else { None }
+    }
+
+    let k = order - 2;
+    let mut id = id.max(1);
+
+    loop {
+        // Split our id into the length bit and the bits of the inner marks
+        let top = 1usize << id.ilog2();
+        let mut inner = id - top;
+
+        // Too many marks: carry the lowest run of ones upwards until we have few enough
+        while inner.count_ones() as usize > k && inner < top {
+            inner += inner & inner.wrapping_neg();
+        }
+
+        // Too few marks: fill in the lowest free positions
+        while (inner.count_ones() as usize) < k && inner < top {
+            inner |= inner + 1;
+        }
+
+        if inner < top && inner.count_ones() as usize == k {
+            return Some(top + inner)
         }
+
+        // No ruler of this length works, try the first ruler one unit longer
+        id = top.checked_mul(2)?;
     }
 }
 
 /* -------------------------------------------------------------------------- */
 /*                          Iterator implementations                          */
 /* -------------------------------------------------------------------------- */
-impl Iterator for RulerIterator {
-    type Item = GolombRuler;
+impl<P: Pruner> Iterator for PrunerIterator<P> {
+    type Item = Ruler;
 
     fn next(&mut self) -> Option<Self::Item> {
-
-        let next_state = self.state.next(self.length);
-        self.state = next_state?;
-        Some(self.state.to_ruler())
+        self.state.advance_with(self.length, &self.pruner, &mut self.scratch).then(|| self.state.to_ruler())
     }
 }
 
-impl Iterator for GolombRulerPrunedIterator {
+impl Iterator for GolombRulerPrefixIterator {
     type Item = GolombRuler;
 
     fn next(&mut self) -> Option<Self::Item> {
 
-        let next_state = self.state.next_pruned(self.order, self.length);
-        self.state = next_state?;
-        Some(self.state.to_ruler())
+        if !self.state.advance_with(self.length, &self.pruner, &mut self.scratch) {
+            return None
+        }
+
+        // Stop once we've left the subtree of our prefix
+        if !self.state.starts_with(&self.prefix) {
+            self.state = vec![true; self.length - 1];
+            return None
+        }
+
+        // Our pruner only keeps golomb rulers
+        Some(GolombRuler::new_unchecked(self.state.to_ruler()))
     }
 }
 
-impl Iterator for GolombRulerDepthIterator {
-    type Item = GolombRuler;
+impl Iterator for RulerIdIterator {
+    type Item = Ruler;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let next_state = self.state.next_golomb_depth_1(self.order, self.length);
-        self.state = next_state?;
-        Some(self.state.to_ruler())
+
+        if let Some(order) = self.order {
+            let id = first_id_with_order(self.id, order)?;
+            if id != self.id {
+                self.seek(id);
+            }
+        }
+
+        if self.id >= self.end_id {
+            return None
+        }
+
+        let ruler = if self.id == 0 {
+            Ruler::from_id(0)
+        } else {
+            self.state.to_ruler()
+        };
+
+        // The ruler [0] has no state, and the empty state belongs to [0, 1]
+        if self.id != 0 {
+            self.state.next_id_mut();
+        }
+        self.id += 1;
+
+        Some(ruler)
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                               Seek implementations                         */
+/* -------------------------------------------------------------------------- */
+impl<P: Pruner> Seek for PrunerIterator<P> {
+    fn seek(&mut self, id: usize) {
+        self.state = seek_state(id, self.length);
+        self.scratch.invalidate();
+    }
+}
+
+impl Seek for GolombRulerPrefixIterator {
+    fn seek(&mut self, id: usize) {
+        // Never seek to a state in front of our subtree
+        self.state = Vec::max(seek_state(id, self.length), self.start.clone());
+        self.scratch.invalidate();
+    }
+}
+
+impl Seek for RulerWalker {
+    fn seek(&mut self, id: usize) {
+        self.state = seek_state(id, self.length);
+        self.scratch.invalidate();
+    }
+}
+
+impl Seek for RulerIdIterator {
+    fn seek(&mut self, id: usize) {
+        RulerIdIterator::seek(self, id)
     }
 }
\ No newline at end of file
@@ -0,0 +1,90 @@
+//! The dual of the OGR problem: for a fixed length `L`, find golomb rulers with as many marks as possible.
+//!
+//! This is the largest Sidon subset of `[0, L]`. We solve it with a branch-and-bound search over the tree
+//! of ruler states, bounding the order reachable below a node with the maximum orders of every shorter length.
+//! Computing those maximums length after length makes every bound exact, so the search proves the maximum.
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+use crate::{GolombRuler, Ruler};
+use super::iterators::PrunerIterator;
+use super::pruners::{Pruner, PartialRuler, Verdict, MarkCount, GolombProperty};
+
+/// Prune nodes below which no ruler can reach `order` marks.
+///
+/// `table[l]` is the maximum order of a golomb ruler of length at most `l`, and must be known for
+/// every length shorter than the rulers of the tree.
+#[derive(Debug)]
+struct MaxOrderBound<'a> {
+    order: usize,
+    table: &'a [usize],
+}
+
+impl Pruner for MaxOrderBound<'_> {
+    fn check(&self, partial: &PartialRuler) -> Verdict {
+
+        if partial.is_leaf {
+            return Verdict::Keep
+        }
+
+        // The marks left to place lie in [depth + 1, length], a shorter ruler
+        let placed = partial.marks.len();
+        let mut bound = placed + self.table[partial.length - partial.depth - 1];
+
+        // Counting from our last mark, the rest of our ruler is a shorter golomb ruler as well
+        let last = partial.last_mark() as usize;
+        if last > 0 {
+            bound = usize::min(bound, placed - 1 + self.table[partial.length - last]);
+        }
+
+        if bound < self.order { Verdict::PruneSubtree } else { Verdict::Keep }
+    }
+
+    fn needs_distances(&self) -> bool {
+        false
+    }
+}
+
+/// Iterator over the golomb rulers of length `length` with `order` marks, using `table` to bound the search
+fn rulers_with_order(length: usize, order: usize, table: &[usize]) -> impl Iterator<Item = GolombRuler> + '_ {
+    let pruner = MarkCount(order).and(GolombProperty).and(MaxOrderBound { order, table });
+    PrunerIterator::new_unchecked(length, pruner).map(GolombRuler::new_unchecked)
+}
+
+/// Return the maximum order of a golomb ruler of length at most `l`, for every `l` up to `max_length`.
+///
+/// The ruler `[0]` gives a maximum order of 1 for a length of 0.
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn max_order_table(max_length: usize) -> Vec<usize> {
+
+    let mut table = vec![1, 2];
+    table.truncate(max_length + 1);
+
+    // Removing the last mark of a ruler leaves a shorter ruler, so the maximum grows by at most one with every length
+    for length in 2..=max_length {
+        let order = table[length - 1] + 1;
+        let found = rulers_with_order(length, order, &table).next().is_some();
+        table.push(if found { order } else { order - 1 });
+    }
+
+    table
+}
+
+/// Return every golomb ruler of length at most `length` with the maximum possible order.
+///
+/// Every translation of these rulers inside `[0, length]` is a largest Sidon subset of `[0, length]`.
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn max_order_rulers(length: usize) -> Vec<GolombRuler> {
+
+    let table = max_order_table(length);
+    let order = table[length];
+
+    (0..=length).filter(|l| table[*l] == order).flat_map(|l| -> Vec<GolombRuler> {
+        match l {
+            // Rulers [0] and [0, 1] have no state to traverse
+            0 | 1 => vec![GolombRuler::new_unchecked(Ruler::from_id(l))],
+            _ => rulers_with_order(l, order, &table).collect(),
+        }
+    }).collect()
+}
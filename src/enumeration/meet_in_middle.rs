@@ -0,0 +1,241 @@
+//! Experimental meet-in-the-middle enumeration of golomb rulers with a fixed length.
+//!
+//! A ruler of order `n` and length `L` splits into a left half, its first `ceil(n / 2)` marks, and a right half,
+//! its last `floor(n / 2)` marks. Mirroring a right half gives a golomb ruler starting at 0, just like a left half,
+//! so both halves come out of the same enumeration of short rulers. Two halves join into a golomb ruler when they
+//! don't overlap, their distances are disjoint, and the distances across them are new.
+//!
+//! Storing every half costs memory, so this only pays off for a middle range of orders. [`MeetInTheMiddleStats`]
+//! reports what was spent.
+
+use std::mem;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+use crate::{GInt, DistanceSet, GolombRuler, Ruler};
+use crate::error::{Result, check_order};
+
+/// Set of distances up to a ruler length, one bit per distance
+#[derive(Clone, Debug)]
+struct DistanceBits(Vec<u64>);
+
+impl DistanceBits {
+
+    fn new(length: usize) -> Self {
+        DistanceBits(vec![0; length / 64 + 1])
+    }
+
+    fn contains(&self, d: usize) -> bool {
+        self.0[d / 64] & (1 << (d % 64)) != 0
+    }
+
+    fn insert(&mut self, d: usize) {
+        self.0[d / 64] |= 1 << (d % 64);
+    }
+
+    fn is_disjoint(&self, other: &DistanceBits) -> bool {
+        self.0.iter().zip(&other.0).all(|(a, b)| a & b == 0)
+    }
+
+    fn union(&self, other: &DistanceBits) -> DistanceBits {
+        DistanceBits(self.0.iter().zip(&other.0).map(|(a, b)| a | b).collect())
+    }
+}
+
+/// A golomb ruler starting at 0, used as a left half or mirrored as a right half
+#[derive(Debug)]
+struct Half {
+    marks: Vec<GInt>,
+    distances: DistanceBits,
+}
+
+impl Half {
+
+    fn last_mark(&self) -> GInt {
+        self.marks[self.marks.len() - 1]
+    }
+
+    fn heap_size(&self) -> usize {
+        self.marks.capacity() * mem::size_of::<GInt>() + self.distances.0.capacity() * mem::size_of::<u64>()
+    }
+}
+
+/// Collect every golomb ruler with `order` marks whose marks lie in `[0, length)`
+fn halves(order: usize, length: usize) -> Vec<Half> {
+
+    fn extend(distances: &mut DistanceSet, order: usize, length: usize, out: &mut Vec<Half>) {
+
+        let marks = distances.marks();
+        if marks.len() == order {
+            let mut bits = DistanceBits::new(length);
+            distances.multiplicities().for_each(|(d, _)| bits.insert(d as usize));
+            out.push(Half { marks: marks.to_vec(), distances: bits });
+            return
+        }
+
+        let last = marks[marks.len() - 1];
+        for p in last + 1..length as GInt {
+            distances.add_mark(p);
+            if distances.is_golomb() {
+                extend(distances, order, length, out);
+            }
+            distances.remove_mark(p);
+        }
+    }
+
+    let mut out = vec![];
+    extend(&mut DistanceSet::from_marks(&[0]), order, length, &mut out);
+    out
+}
+
+/// Join the left half `left` with the mirror of `right` on a ruler of length `length`, if that gives a golomb ruler.
+/// Both halves must fit side by side.
+fn join(left: &Half, right: &Half, length: usize) -> Option<GolombRuler> {
+
+    let length = length as GInt;
+    if !left.distances.is_disjoint(&right.distances) {
+        return None
+    }
+
+    let mut distances = left.distances.union(&right.distances);
+    let right_marks = right.marks.iter().rev().map(|m| length - m);
+
+    for r in right_marks.clone() {
+        for l in &left.marks {
+            let d = (r - l) as usize;
+            if distances.contains(d) {
+                return None
+            }
+            distances.insert(d);
+        }
+    }
+
+    let marks: Vec<GInt> = left.marks[1..].iter().copied().chain(right_marks).collect();
+    Some(GolombRuler::new_unchecked(Ruler::new(&marks)))
+}
+
+/// Memory and time spent by [`meet_in_the_middle`]
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "python", pyclass)]
+pub struct MeetInTheMiddleStats {
+    /// Number of left halves
+    pub left_halves: usize,
+    /// Number of right halves, before mirroring
+    pub right_halves: usize,
+    /// Number of pairs of halves that fit side by side and were tried
+    pub pairs: usize,
+    /// Number of golomb rulers found
+    pub rulers: usize,
+    /// Bytes used to store the halves
+    pub memory: usize,
+    pub build_time: Duration,
+    pub join_time: Duration,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl MeetInTheMiddleStats {
+
+    #[getter]
+    fn left_halves(&self) -> usize {
+        self.left_halves
+    }
+
+    #[getter]
+    fn right_halves(&self) -> usize {
+        self.right_halves
+    }
+
+    #[getter]
+    fn pairs(&self) -> usize {
+        self.pairs
+    }
+
+    #[getter]
+    fn rulers(&self) -> usize {
+        self.rulers
+    }
+
+    #[getter]
+    fn memory(&self) -> usize {
+        self.memory
+    }
+
+    /// Seconds spent enumerating the halves
+    #[getter(build_time)]
+    fn py_build_time(&self) -> f64 {
+        self.build_time.as_secs_f64()
+    }
+
+    /// Seconds spent joining the halves
+    #[getter(join_time)]
+    fn py_join_time(&self) -> f64 {
+        self.join_time.as_secs_f64()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "MeetInTheMiddleStats(left_halves={}, right_halves={}, pairs={}, rulers={}, memory={}, build_time={:?}, join_time={:?})",
+            self.left_halves, self.right_halves, self.pairs, self.rulers, self.memory, self.build_time, self.join_time
+        )
+    }
+}
+
+/// Enumerate the golomb rulers with `order` marks and length `length` by joining precomputed halves.
+///
+/// Gives the same rulers as filtering [`enumerate_golomb_rulers_pruned_with_length`](super::enumerate_golomb_rulers_pruned_with_length)
+/// with the golomb property, sorted, along with the memory and time it took. Order 1 gives no ruler.
+pub fn meet_in_the_middle(order: usize, length: usize) -> Result<(Vec<GolombRuler>, MeetInTheMiddleStats)> {
+
+    check_order(order)?;
+
+    let mut stats = MeetInTheMiddleStats::default();
+    if order < 2 || length == 0 {
+        return Ok((vec![], stats))
+    }
+
+    let start = Instant::now();
+    let left_order = order.div_ceil(2);
+    let right_order = order / 2;
+
+    // Sorting by last mark lets us stop at the first right half that overlaps a left half
+    let sorted = |mut halves: Vec<Half>| { halves.sort_by_key(Half::last_mark); halves };
+    let left = sorted(halves(left_order, length));
+    let own_right = if right_order == left_order { None } else { Some(sorted(halves(right_order, length))) };
+    let right = own_right.as_ref().unwrap_or(&left);
+
+    stats.left_halves = left.len();
+    stats.right_halves = right.len();
+    // Halves of the same order are only stored once
+    stats.memory = [Some(&left), own_right.as_ref()].into_iter().flatten().map(|halves| {
+        halves.capacity() * mem::size_of::<Half>() + halves.iter().map(Half::heap_size).sum::<usize>()
+    }).sum();
+    stats.build_time = start.elapsed();
+
+    let start = Instant::now();
+    let mut rulers = vec![];
+
+    for l in &left {
+        for r in right.iter().take_while(|r| l.last_mark() + r.last_mark() < length as GInt) {
+            stats.pairs += 1;
+            rulers.extend(join(l, r, length));
+        }
+    }
+
+    rulers.sort();
+    stats.rulers = rulers.len();
+    stats.join_time = start.elapsed();
+
+    Ok((rulers, stats))
+}
+
+/// Experimental: enumerate the golomb rulers with `order` marks and length `length` by joining left and right halves.
+///
+/// Returns the sorted rulers along with a `MeetInTheMiddleStats` reporting the memory and time spent.
+#[cfg(feature = "python")]
+#[pyfunction]
+pub fn enumerate_golomb_rulers_meet_in_the_middle(order: usize, length: usize) -> Result<(Vec<GolombRuler>, MeetInTheMiddleStats)> {
+    meet_in_the_middle(order, length)
+}
@@ -2,115 +2,222 @@
 
 mod state;
 mod iterators;
+mod constraints;
+mod pruners;
+mod query;
+mod max_order;
+mod meet_in_middle;
+mod estimate;
+mod search_tree;
 
-use std::vec;
+use std::collections::BTreeMap;
+
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
-use thiserror::Error;
 
-use crate::GInt;
-use crate::GolombRuler;
+use crate::{GInt, GolombRuler, Ruler};
+use crate::error::{Result, check_order};
 
 use iterators::*;
 
-#[derive(Error, Debug)]
-pub enum GolombIterationError {
-    #[error("The current golomb ruler index can't be contained in a u64")]
-    IndexOverflow
-}
-
+pub use constraints::RulerConstraints;
+pub use query::{RulerQuery, Pruning, OutputMode, QueryOutput};
+pub use iterators::{Seek, PrunerIterator, GolombRulerPrefixIterator, RulerIdIterator, RulerWalker, WalkMode};
+pub use max_order::{max_order_table, max_order_rulers};
+pub use search_tree::{NodeOutcome, TreeNode, SearchTree, TreeRecorder, MAX_TREE_LENGTH, record_search_tree};
+pub use estimate::{estimate_golomb_rulers_pruned, TreeEstimate};
+pub use meet_in_middle::{meet_in_the_middle, MeetInTheMiddleStats};
+pub use pruners::{Pruner, Verdict, PartialRuler, NoPruning, MarkCount, FirstOrderDistances, DistanceOrders, GolombProperty, MaxMultiplicity, MaxCollisions, Maximal, And, Or};
+#[cfg(feature = "python")]
+pub use query::RulerQueryIterator;
+#[cfg(feature = "python")]
+pub use search_tree::export_search_tree;
+#[cfg(feature = "python")]
+pub use estimate::py_estimate_golomb_rulers_pruned;
+#[cfg(feature = "python")]
+pub use meet_in_middle::enumerate_golomb_rulers_meet_in_the_middle;
+#[cfg(feature = "python")]
+pub use pruners::{PyPruner, PyPartialRuler};
 
 /// Exhaustively enumerate all rulers up to length `max_length`
 ///
 /// # Arguments
 /// * `max_length`: The maximum length
-#[pyfunction]
-pub fn enumerate_rulers(max_length: usize) -> Vec<GolombRuler> {
-    let all_rulers: Vec<GolombRuler> = (2..max_length + 1).flat_map(|length| {
-        RulerIterator::new(length)
-    }).collect();
-
-    all_rulers
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn enumerate_rulers(max_length: usize) -> Vec<Ruler> {
+    RulerQuery::new().lengths(2, max_length).collect()
 }
 
-#[pyfunction]
-pub fn enumerate_rulers_with_length(length: usize) -> Vec<GolombRuler> {
-    match length {
-        0 => vec![GolombRuler::from_id(0)],
-        1 => vec![GolombRuler::from_id(1)],
-        _ => RulerIterator::new(length).collect()
-    }
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn enumerate_rulers_with_length(length: usize) -> Vec<Ruler> {
+    RulerQuery::new().length(length).collect()
 }
 
-#[pyfunction]
-pub fn enumerate_pruned_rulers(order: usize, length: usize) -> Vec<GolombRuler> {
-    if order == 2 { return vec![GolombRuler::new(&[length as GInt])] }
-    GolombRulerPrunedIterator::new(order, length).collect()
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn enumerate_pruned_rulers(order: usize, length: usize) -> Result<Vec<Ruler>> {
+    check_order(order)?;
+    Ok(RulerQuery::new().order(order).length(length).pruning(Pruning::MarkCount).collect())
 }
 
 /// Get all rulers with `order` marks. They do not have to have the golomb property.
-#[pyfunction]
-pub fn enumerate_rulers_with_order(order: usize, length: usize) -> Vec<GolombRuler> {
-    let rulers = enumerate_rulers(length);
-    rulers.into_iter().filter(|r| r.order() == order).collect()
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn enumerate_rulers_with_order(order: usize, length: usize) -> Result<Vec<Ruler>> {
+    check_order(order)?;
+    Ok(RulerQuery::new().order(order).lengths(2, length).pruning(Pruning::MarkCount).collect())
 }
 
 /// Print out every possible golomb ruler of order `order`
 ///
-#[pyfunction]
-pub fn enumerate_golomb_rulers(order: usize, max_length: usize) -> Vec<GolombRuler> {
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn enumerate_golomb_rulers(order: usize, max_length: usize) -> Result<Vec<GolombRuler>> {
+    check_order(order)?;
+    Ok(RulerQuery::new().order(order).lengths(2, max_length).collect_golomb())
+}
 
-    let all_rulers: Vec<GolombRuler> = (2..max_length + 1).flat_map(|length| {
-        RulerIterator::new(length)
-    }).collect();
 
-    all_rulers.into_iter().filter(|r| r.order() == order && r.is_golomb_ruler()).collect()
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn enumerate_golomb_rulers_with_length(order: usize, length: usize) -> Result<Vec<GolombRuler>> {
+    check_order(order)?;
+    Ok(RulerQuery::new().order(order).length(length).collect_golomb())
 }
 
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn enumerate_golomb_rulers_pruned(order: usize, max_length: usize) -> Result<Vec<GolombRuler>> {
+    check_order(order)?;
+    Ok(RulerQuery::new().order(order).lengths(2, max_length).pruning(Pruning::MarkCount).collect_golomb())
+}
 
-#[pyfunction]
-pub fn enumerate_golomb_rulers_with_length(order: usize, length: usize) -> Vec<GolombRuler> {
-    let all_rulers: Vec<GolombRuler> = RulerIterator::new(length).collect();
-    all_rulers.into_iter().filter(|r| r.order() == order && r.is_golomb_ruler() && r.length() == length as GInt).collect()
+/// Enumerate all golomb rulers with order `order` and length `length`
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn enumerate_golomb_rulers_pruned_with_length(order: usize, length: usize) -> Result<Vec<GolombRuler>> {
+    check_order(order)?;
+    Ok(RulerQuery::new().order(order).length(length).pruning(Pruning::MarkCount).collect_golomb())
 }
 
-#[pyfunction]
-pub fn enumerate_golomb_rulers_pruned(order: usize, max_length: usize) -> Vec<GolombRuler> {
+/// For initial enumeration, check the golomb property at a depth of `depth`.
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn enumerate_golomb_rulers_depth(order: usize, max_length: usize, depth: usize) -> Result<Vec<GolombRuler>> {
+    check_order(order)?;
+    Ok(RulerQuery::new().order(order).lengths(2, max_length).pruning(Pruning::Depth(depth)).collect_golomb())
+}
+
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn enumerate_golomb_rulers_depth_with_length(order: usize, length: usize, depth: usize) -> Result<Vec<GolombRuler>> {
+    check_order(order)?;
+    Ok(RulerQuery::new().order(order).length(length).pruning(Pruning::Depth(depth)).collect_golomb())
+}
 
-    let all_rulers: Vec<GolombRuler> = (2..max_length + 1).flat_map(|length| {
-        GolombRulerPrunedIterator::new(order, length)
-    }).collect();
+/// Enumerate every golomb ruler of order `order` up to length `max_length` that satisfies `constraints`.
+///
+/// Constraints are applied while traversing the tree of ruler states, not as a filter afterwards.
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn enumerate_golomb_rulers_constrained(order: usize, max_length: usize, constraints: RulerConstraints) -> Result<Vec<GolombRuler>> {
+    check_order(order)?;
+    Ok(RulerQuery::new().order(order).lengths(2, max_length).pruning(Pruning::Backtracking).constraints(constraints).collect_golomb())
+}
 
-    // all_rulers
-    // let filtered: Vec<GolombRuler> = all_rulers.into_iter().filter(|r| r.is_golomb_ruler()).collect();
-    // for ruler in filtered.clone().into_iter() {
-    //     dbg!(ruler.length());
-    // }
+/// Enumerate every golomb ruler with order `order` and length `length` that satisfies `constraints`.
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn enumerate_golomb_rulers_constrained_with_length(order: usize, length: usize, constraints: RulerConstraints) -> Result<Vec<GolombRuler>> {
+    check_order(order)?;
+    Ok(RulerQuery::new().order(order).length(length).pruning(Pruning::Backtracking).constraints(constraints).collect_golomb())
+}
+
+/// Enumerate every golomb ruler of order `order` up to length `max_length` whose first marks are `prefix`.
+///
+/// The prefix must start at 0, e.g. `[0, 4, 6]`, and have the golomb property itself.
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn enumerate_golomb_rulers_with_prefix(prefix: Vec<GInt>, order: usize, max_length: usize) -> Result<Vec<GolombRuler>> {
 
-    // filtered
-    all_rulers.iter().filter(|g| g.is_golomb_ruler()).cloned().collect()
+    check_order(order)?;
+    validate_prefix(&prefix)?;
+
+    let iterators = (2..max_length + 1).map(|length| {
+        GolombRulerPrefixIterator::new(&prefix, order, length)
+    }).collect::<Result<Vec<GolombRulerPrefixIterator>>>()?;
+
+    Ok(iterators.into_iter().flatten().collect())
 }
 
-/// Enumerate all rulers with order `order` and length `length`
-#[pyfunction]
-pub fn enumerate_golomb_rulers_pruned_with_length(order: usize, length: usize) -> Vec<GolombRuler> {
-    GolombRulerPrunedIterator::new(order, length).collect()
+/// Enumerate every ruler of order `order` up to length `max_length` where no distance appears more than `lambda_` times,
+/// and with at most `max_collisions` pairs of marks repeating the distance of another pair.
+///
+/// Both limits prune the tree of ruler states. Without any limit, a multiplicity of 1 gives the golomb rulers.
+#[cfg_attr(feature = "python", pyfunction, pyo3(signature = (order, max_length, lambda_=None, max_collisions=None)))]
+pub fn enumerate_near_golomb_rulers(order: usize, max_length: usize, lambda_: Option<usize>, max_collisions: Option<usize>) -> Result<Vec<Ruler>> {
+
+    check_order(order)?;
+
+    let lambda_ = match (lambda_, max_collisions) {
+        (None, None) => Some(1),
+        _ => lambda_,
+    };
+
+    let pruner = MaxMultiplicity(lambda_.unwrap_or(usize::MAX)).and(MaxCollisions(max_collisions.unwrap_or(usize::MAX)));
+    Ok(RulerQuery::new().order(order).lengths(2, max_length).pruning(Pruning::MarkCount).with_pruner(pruner).collect())
 }
 
-/// For initial enumeration, check the golomb property at a depth of `depth`.
-#[pyfunction]
-fn enumerate_golomb_rulers_depth(order: usize, max_length: usize, depth: usize) -> Vec<GolombRuler> {
+/// Enumerate every maximal golomb ruler of length `length`, optionally limited to orders between `min_order` and `max_order`.
+///
+/// A golomb ruler is maximal when no mark can be added inside `[0, length]` without breaking the golomb property.
+#[cfg_attr(feature = "python", pyfunction, pyo3(signature = (length, min_order=None, max_order=None)))]
+pub fn enumerate_maximal_golomb_rulers(length: usize, min_order: Option<usize>, max_order: Option<usize>) -> Vec<GolombRuler> {
+    maximal_golomb_rulers(length, min_order, max_order).collect()
+}
+
+/// Count the maximal golomb rulers of length `length`, by order.
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn count_maximal_golomb_rulers(length: usize) -> BTreeMap<usize, usize> {
+    let mut counts = BTreeMap::new();
+    maximal_golomb_rulers(length, None, None).for_each(|r| *counts.entry(r.order()).or_insert(0) += 1);
+    counts
+}
 
-    let all_rulers: Vec<GolombRuler> = (2..max_length + 1).flat_map(|length| {
-        GolombRulerDepthIterator::new(order, length, depth)
-    }).collect();
+fn maximal_golomb_rulers(length: usize, min_order: Option<usize>, max_order: Option<usize>) -> impl Iterator<Item = GolombRuler> {
+    RulerQuery::new()
+        .orders(min_order.unwrap_or(1), max_order.unwrap_or(usize::MAX))
+        .length(length)
+        .pruning(Pruning::Backtracking)
+        .with_pruner(Maximal)
+        .iter_golomb()
+}
 
-    all_rulers
+/// Enumerate every ruler with an id in `[start_id, end_id)`, optionally keeping only those for which `filter(ruler)` is true.
+///
+/// Iteration starts directly at `start_id`, so disjoint id ranges can be enumerated by independent processes.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(signature = (start_id, end_id, filter=None))]
+pub fn enumerate_ids(start_id: usize, end_id: usize, filter: Option<&PyAny>) -> PyResult<Vec<Ruler>> {
+
+    let rulers = RulerIdIterator::new(start_id, end_id, None);
+
+    match filter {
+        None => Ok(rulers.collect()),
+        Some(f) => {
+            let mut out = Vec::new();
+            for r in rulers {
+                if f.call1((r.clone(),))?.is_true()? {
+                    out.push(r);
+                }
+            }
+            Ok(out)
+        }
+    }
 }
 
-fn enumerate_golomb_rulers_depth_with_length(order: usize, length: usize, depth: usize) -> Vec<GolombRuler> {
-    GolombRulerDepthIterator::new(order, length, depth).collect()
+/// Enumerate every golomb ruler with an id in `[start_id, end_id)`
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn enumerate_golomb_ids(start_id: usize, end_id: usize) -> Vec<GolombRuler> {
+    RulerIdIterator::new(start_id, end_id, None).filter_map(|r| GolombRuler::try_from(r).ok()).collect()
 }
 
+/// Enumerate every ruler with order `order` and an id in `[start_id, end_id)`
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn enumerate_ids_with_order(start_id: usize, end_id: usize, order: usize) -> Result<Vec<Ruler>> {
+    check_order(order)?;
+    Ok(RulerIdIterator::new(start_id, end_id, Some(order)).collect())
+}
 
 /* -------------------------------------------------------------------------- */
 /*                              Public functions                              */
@@ -121,18 +228,18 @@ mod tests {
     use super::*;
     use crate::enumeration::state::RulerState;
 
-    #[test]
-    fn test() {
-
-        let my_bool: Vec<bool> = vec![true, true, false];
-        dbg!(my_bool.to_u64());
-        assert_eq!(my_bool.count_marks(), 2);
-        dbg!(my_bool.to_string());
-
+    fn to_strings<R: ToString>(rulers: Vec<R>) -> Vec<String> {
+        rulers.iter().map(|r| r.to_string()).collect()
     }
 
+    fn sorted_strings<R: ToString>(rulers: Vec<R>) -> Vec<String> {
+        let mut strings = to_strings(rulers);
+        strings.sort();
+        strings
+    }
 
     #[test]
+    #[allow(unused_variables)]
     fn test_enum() {
 
         // let my_bool: Vec<bool> = vec![true, true, false];
@@ -143,22 +250,22 @@ mod tests {
         // dbg!(my_bool.to_ruler());
 
 
-        // dbg!(enumerate_golomb_rulers(3, 4));
+        // dbg!(enumerate_golomb_rulers(3, 4).unwrap());
 
         let order = 3;
 
-        // let len_1 = enumerate_golomb_rulers(order, 1);
+        // let len_1 = enumerate_golomb_rulers(order, 1).unwrap();
 
         // dbg!(len_1);
 
-        // let len_2 = enumerate_golomb_rulers(order, 2);
+        // let len_2 = enumerate_golomb_rulers(order, 2).unwrap();
         // dbg!(len_2);
 
-        // let len_3 = enumerate_golomb_rulers(order, 3);
+        // let len_3 = enumerate_golomb_rulers(order, 3).unwrap();
         // dbg!(len_3);
 
 
-        let r_o3_l3 = enumerate_golomb_rulers(2, 3);
+        let r_o3_l3 = enumerate_golomb_rulers(2, 3).unwrap();
 
         for r in r_o3_l3 {
             println!("{}", r);
@@ -178,7 +285,7 @@ mod tests {
         }
 
 
-        // let r_o2_3 = enumerate_golomb_rulers(2, 3);
+        // let r_o2_3 = enumerate_golomb_rulers(2, 3).unwrap();
         let r3 = enumerate_rulers_with_length(3);
         println!("========= Length 3 ===========");
         for r in r3 {
@@ -193,13 +300,13 @@ mod tests {
 
 
         println!("========= G 3_10 ===========");
-        let g4 = enumerate_golomb_rulers(3, 10);
+        let g4 = enumerate_golomb_rulers(3, 10).unwrap();
         for r in g4 {
             println!("{}", r)
         }
 
         println!("======== Ruler 3_10 ========");
-        let r_3_10 = enumerate_rulers_with_order(3, 10);
+        let r_3_10 = enumerate_rulers_with_order(3, 10).unwrap();
         for r in r_3_10.iter() {
             println!("{}", r);
         }
@@ -220,7 +327,7 @@ mod tests {
         let mut lengths: Vec<(usize, usize)> = Vec::new();
         let order_range = 2..10;
         for order in order_range.clone() {
-            lengths.push((order, enumerate_golomb_rulers(order, 15).len()));
+            lengths.push((order, enumerate_golomb_rulers(order, 15).unwrap().len()));
 
         }
 
@@ -239,7 +346,7 @@ mod tests {
         let length = 10;
         let rulers = enumerate_rulers_with_length(length);
 
-        let rulers: Vec<&GolombRuler> = rulers.iter().filter(|r| r.is_golomb_ruler()).collect();
+        let rulers: Vec<&Ruler> = rulers.iter().filter(|r| r.is_golomb_ruler()).collect();
 
 
         for r in &rulers {
@@ -254,7 +361,7 @@ mod tests {
 
         let length = 5;
         let order = 3;
-        let rulers = enumerate_pruned_rulers(order, length);
+        let rulers = enumerate_pruned_rulers(order, length).unwrap();
 
         for r in &rulers {
             println!("{}", r);
@@ -268,17 +375,6 @@ mod tests {
         // dbg!(iter.take(1).collect::<Vec<GolombRuler>>());
 
 
-    }
-
-    #[test]
-    fn test_jump_back() {
-
-        let test: Vec<bool> = vec![false, true, false];
-
-        dbg!(&test);
-        dbg!(test.jump_back());
-
-
     }
 
     #[test]
@@ -304,26 +400,26 @@ mod tests {
     #[test]
     fn test_from_id() {
 
-        let first = GolombRuler::from_id(0);
+        let first = Ruler::from_id(0);
         println!("{}", first);
 
-        let second = GolombRuler::from_id(1);
+        let second = Ruler::from_id(1);
         println!("{}", second);
 
-        let third = GolombRuler::from_id(2);
+        let third = Ruler::from_id(2);
         println!("{}", third);
 
-        let third = GolombRuler::from_id(3);
+        let third = Ruler::from_id(3);
         println!("{}", third);
 
-        let fourth = GolombRuler::from_id(4);
+        let fourth = Ruler::from_id(4);
         println!("{}", fourth);
 
-        // let test = GolombRuler::from_id(1000234);
+        // let test = Ruler::from_id(1000234);
         // println!("{}", test);
 
         for i in 0..10 {
-            println!("[{:02}] {}", i, GolombRuler::from_id(i));
+            println!("[{:02}] {}", i, Ruler::from_id(i));
         }
 
     }
@@ -333,8 +429,8 @@ mod tests {
 
         let max_range = 1000;
 
-        let rulers = (0..max_range).map(GolombRuler::from_id).collect::<Vec<GolombRuler>>();
-        let rulers_is_golom: Vec<(&GolombRuler, bool)> = rulers.iter().map(|r| (r, r.is_golomb_ruler())).collect();
+        let rulers = (0..max_range).map(Ruler::from_id).collect::<Vec<Ruler>>();
+        let rulers_is_golom: Vec<(&Ruler, bool)> = rulers.iter().map(|r| (r, r.is_golomb_ruler())).collect();
 
         let mut golomb_rulers: Vec<usize> = Vec::new();
 
@@ -355,8 +451,8 @@ mod tests {
     fn stats() {
 
         let max_range = 100;
-        let rulers = GolombRuler::from_ids(0, max_range);
-        let golomb_rulers = rulers.iter().filter(|r| r.is_golomb_ruler()).collect::<Vec<&GolombRuler>>();
+        let rulers = Ruler::from_ids(0, max_range);
+        let golomb_rulers = rulers.iter().filter(|r| r.is_golomb_ruler()).collect::<Vec<&Ruler>>();
 
         // Find the average order?
         // Actually I just want the _distribution_ of heights.
@@ -370,33 +466,15 @@ mod tests {
 
 
 
-    }
-
-    /// Verify that our `contains` function for `Vec<bool>` works properly
-    #[test]
-    fn test_contains() {
-
-        let ruler = GolombRuler::from_id(10);
-        println!("Ruler: {}", ruler);
-
-        let state = ruler.to_state();
-        assert!(state.contains(0));
-        assert!(state.contains(2));
-        assert!(state.contains(4));
-        assert!(!state.contains(5));
-        assert!(!state.contains(10));
-        assert!(!state.contains(200));
-        assert!(!state.contains(-2));
-
     }
 
     #[test]
     fn test_propery_order_1() {
 
-        let ruler = GolombRuler::from_id(10);
+        let ruler = Ruler::from_id(10);
         println!("Ruler: {}", ruler);
 
-        let r_09 = GolombRuler::from_id(9);
+        let r_09 = Ruler::from_id(9);
         println!("Ruler: {}", r_09);
 
         assert!(!ruler.is_golomb_ruler_order_1());
@@ -405,9 +483,10 @@ mod tests {
     }
 
     #[test]
+    #[allow(unused_variables)]
     fn add_mark_unit() {
 
-        let ruler = GolombRuler::from_id(22528);
+        let ruler = Ruler::from_id(22528);
 
         // dbg!(ruler.to_state());
         // dbg!(ruler.to_state().next_pruned(4, 15).unwrap());
@@ -421,8 +500,8 @@ mod tests {
     #[test]
     fn add_mark() {
 
-        // let ruler = GolombRuler::from_id(18432);
-        // let ruler = GolombRuler::from_id(576);
+        // let ruler = Ruler::from_id(18432);
+        // let ruler = Ruler::from_id(576);
 
         // println!("R.state: {:?}, r: {}", ruler.to_state(), ruler);
         // let next = ruler.to_state().next_pruned(4, 15).unwrap().to_ruler();
@@ -432,7 +511,7 @@ mod tests {
 
         // println!("R.state.next(): {:?}, r: {}", next.to_state(), next);
 
-        let rulers = enumerate_golomb_rulers_pruned_with_length(4, 15);
+        let rulers = enumerate_golomb_rulers_pruned_with_length(4, 15).unwrap();
         for r in rulers {
             println!("{}", r);
         }
@@ -450,15 +529,15 @@ mod tests {
         let rulers = enumerate_rulers_with_length(length);
         println!("N rulers: {} length: {}", rulers.len(), length);
 
-        let rulers_pruned = enumerate_golomb_rulers_pruned_with_length(order, length);
+        let rulers_pruned = enumerate_golomb_rulers_pruned_with_length(order, length).unwrap();
         println!("N pruned rulers: {} (order: {}, length: {})", rulers_pruned.len(), order, length);
 
         let ruler_pruned_g = rulers_pruned.iter().filter(|r| r.is_golomb_ruler()).collect_vec();
 
-        let rulers_depth = enumerate_golomb_rulers_depth_with_length(order, length, depth);
+        let rulers_depth = enumerate_golomb_rulers_depth_with_length(order, length, depth).unwrap();
         println!("N depth rulers: {} (order: {}, length: {})", rulers_depth.len(), order, length);
 
-        let golomb_rulers = enumerate_golomb_rulers_with_length(order, length);
+        let golomb_rulers = enumerate_golomb_rulers_with_length(order, length).unwrap();
         println!("N golomb rulers: {} (order: {}, length: {})", golomb_rulers.len(), order, length);
 
         for r in &rulers_pruned {
@@ -500,7 +579,7 @@ mod tests {
 
                 // Generate all rulers then filter
                 let filtered = all_rulers.iter().filter(|r| r.order() == order).collect_vec();
-                let pruned = enumerate_pruned_rulers(order, length);
+                let pruned = enumerate_pruned_rulers(order, length).unwrap();
 
                 assert_eq!(filtered.len(), pruned.len())
             }
@@ -514,15 +593,828 @@ mod tests {
     #[test]
     fn timing_tnp() {
 
-        enumerate_pruned_rulers(10, 20);
+        enumerate_pruned_rulers(10, 20).unwrap();
     }
 
     #[test]
     fn timing_all() {
 
-        // enumerate_pruned_rulers(10, 20);
-        enumerate_rulers_with_order(10, 20);
+        // enumerate_pruned_rulers(10, 20).unwrap();
+        enumerate_rulers_with_order(10, 20).unwrap();
+    }
+
+    #[test]
+    fn constrained() {
+
+        let order = 5;
+        let max_length = 16;
+
+        let constraint_sets = vec![
+            RulerConstraints::default(),
+            RulerConstraints::new(vec![3, 7], vec![], vec![], None, None),
+            RulerConstraints::new(vec![], vec![4], vec![], None, None),
+            RulerConstraints::new(vec![], vec![], vec![1, 5], None, None),
+            RulerConstraints::new(vec![], vec![], vec![], Some(3), Some(8)),
+            RulerConstraints::new(vec![2], vec![6], vec![13], Some(2), None),
+        ];
+
+        for constraints in constraint_sets {
+
+            let expected: Vec<String> = enumerate_golomb_rulers(order, max_length).unwrap()
+                .into_iter()
+                .filter(|r| constraints.admits(r))
+                .map(|r| r.to_string())
+                .collect();
+
+            let constrained: Vec<String> = enumerate_golomb_rulers_constrained(order, max_length, constraints.clone()).unwrap()
+                .into_iter()
+                .map(|r| r.to_string())
+                .collect();
+
+            assert_eq!(expected, constrained, "{:?}", constraints);
+        }
+
+        // The ruler [0, 1, 4, 9, 11] is the only optimal golomb ruler of order 5, up to mirroring
+        let optimal = enumerate_golomb_rulers_constrained_with_length(5, 11, RulerConstraints::new(vec![], vec![1], vec![], None, None)).unwrap();
+        assert_eq!(optimal.len(), 1);
+        assert_eq!(optimal[0].to_string(), "[0, 1, 4, 9, 11]");
     }
 
+    #[test]
+    fn id_ranges() {
+
+        use itertools::*;
+
+
+        for (start, end) in [(0, 100), (37, 512), (1000, 3000)] {
+
+            let expected = Ruler::from_ids(start, end);
+            assert_eq!(to_strings(RulerIdIterator::new(start, end, None).collect_vec()), to_strings(expected.clone()));
+            #[cfg(feature = "python")]
+            assert_eq!(to_strings(enumerate_ids(start, end, None).unwrap()), to_strings(expected.clone()));
 
-}
\ No newline at end of file
+            let golomb = expected.iter().filter(|r| r.is_golomb_ruler()).cloned().collect_vec();
+            assert_eq!(to_strings(enumerate_golomb_ids(start, end)), to_strings(golomb));
+
+            for order in 1..8 {
+                let with_order = expected.iter().filter(|r| r.order() == order).cloned().collect_vec();
+                assert_eq!(to_strings(enumerate_ids_with_order(start, end, order).unwrap()), to_strings(with_order));
+            }
+        }
+
+        // Shards cover every id exactly once
+        let shards = (0..8).flat_map(|i| RulerIdIterator::new(i * 128, (i + 1) * 128, None)).collect_vec();
+        assert_eq!(to_strings(shards), to_strings(Ruler::from_ids(0, 1024)));
+    }
+
+    #[test]
+    fn seek() {
+
+        let length = 9;
+        let order = 4;
+        let constraints = RulerConstraints::new(vec![], vec![], vec![], Some(2), None);
+
+        fn check_seek<R: AsRef<Ruler>, I: Iterator<Item = R> + Seek>(make_iter: impl Fn() -> I) {
+
+            let all = make_iter().map(|r| r.as_ref().clone()).collect::<Vec<Ruler>>();
+
+            for (idx, r) in all.iter().enumerate() {
+                let mut iter = make_iter();
+                iter.seek(r.to_id().unwrap());
+                let rest = iter.map(|r| r.as_ref().to_string()).collect::<Vec<String>>();
+                let expected = all[idx..].iter().map(|r| r.to_string()).collect::<Vec<String>>();
+                assert_eq!(rest, expected);
+            }
+
+            // Shorter rulers restart the iteration, longer rulers end it
+            let mut iter = make_iter();
+            iter.seek(3);
+            assert_eq!(iter.count(), all.len());
+
+            let mut iter = make_iter();
+            iter.seek(1 << 20);
+            assert_eq!(iter.count(), 0);
+        }
+
+        check_seek(|| PrunerIterator::with_pruner(length, NoPruning).unwrap());
+        check_seek(|| PrunerIterator::with_pruner(length, MarkCount(order)).unwrap());
+        check_seek(|| PrunerIterator::with_pruner(length, MarkCount(order).and(FirstOrderDistances)).unwrap());
+        check_seek(|| PrunerIterator::with_pruner(length, MarkCount(order).and(GolombProperty).and(constraints.clone())).unwrap());
+        check_seek(|| RulerIdIterator::new(0, 1 << 10, Some(order)));
+        check_seek(|| GolombRulerPrefixIterator::new(&[0, 2], order, length).unwrap());
+    }
+
+    #[test]
+    fn prefix() {
+
+        let order = 5;
+        let max_length = 15;
+        let all = enumerate_golomb_rulers(order, max_length).unwrap();
+
+        for prefix in [vec![0], vec![0, 1], vec![0, 2, 7], vec![0, 3, 4, 9], vec![0, 1, 4, 9, 11], vec![0, 13]] {
+
+            let expected: Vec<String> = all.iter()
+                .filter(|r| r.order() >= prefix.len() && (1..prefix.len()).all(|i| r.marks[i - 1] == prefix[i]))
+                .map(|r| r.to_string())
+                .collect();
+
+            let with_prefix: Vec<String> = enumerate_golomb_rulers_with_prefix(prefix.clone(), order, max_length).unwrap()
+                .iter()
+                .map(|r| r.to_string())
+                .collect();
+
+            assert_eq!(expected, with_prefix, "{:?}", prefix);
+        }
+
+        assert!(enumerate_golomb_rulers_with_prefix(vec![0, 1, 2], order, max_length).is_err());
+        assert!(enumerate_golomb_rulers_with_prefix(vec![1, 3], order, max_length).is_err());
+        assert!(enumerate_golomb_rulers_with_prefix(vec![0, 3, 2], order, max_length).is_err());
+        assert!(enumerate_golomb_rulers_with_prefix(vec![], order, max_length).is_err());
+
+        // A single length at a time
+        let iter = GolombRulerPrefixIterator::new(&[0, 1, 4], 4, 9).unwrap();
+        assert_eq!(iter.map(|r| r.to_string()).collect::<Vec<_>>(), vec!["[0, 1, 4, 9]"]);
+        assert!(GolombRulerPrefixIterator::new(&[0, 1], 0, 9).is_err());
+        assert!(GolombRulerPrefixIterator::new(&[0, 1], 4, 1).is_err());
+    }
+
+    #[test]
+    fn walker() {
+
+        let length = 12;
+        let constraints = RulerConstraints::new(vec![5], vec![], vec![], None, Some(6));
+
+        let walk = |mode: WalkMode| {
+            let mut out = vec![];
+            RulerWalker::new(length, mode).unwrap().visit(|marks| out.push(format!("{:?}", marks)));
+            out
+        };
+
+        assert_eq!(walk(WalkMode::All), to_strings(enumerate_rulers_with_length(length)));
+
+        for order in 2..7 {
+            assert_eq!(walk(WalkMode::Order(order)), to_strings(enumerate_pruned_rulers(order, length).unwrap()));
+            assert_eq!(walk(WalkMode::Golomb(order)), to_strings(enumerate_golomb_rulers_with_length(order, length).unwrap()));
+            assert_eq!(
+                walk(WalkMode::Constrained(order, Box::new(constraints.clone()))),
+                to_strings(enumerate_golomb_rulers_constrained_with_length(order, length, constraints.clone()).unwrap())
+            );
+        }
+
+        // The lending interface reuses the same buffer for every ruler
+        let mut walker = RulerWalker::new(length, WalkMode::Golomb(5)).unwrap();
+        let first = walker.next_marks().unwrap().as_ptr();
+        let second = walker.next_marks().unwrap().as_ptr();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn query() {
+
+        use itertools::*;
+
+        let golomb = RulerQuery::new().orders(3, 5).lengths(0, 13).golomb(true);
+
+        // Every pruning strategy finds the same golomb rulers
+        let expected = sorted_strings(golomb.collect());
+        for pruning in [Pruning::MarkCount, Pruning::Depth(0), Pruning::Depth(1), Pruning::Depth(3), Pruning::Backtracking] {
+            assert_eq!(sorted_strings(golomb.pruning(pruning).collect()), expected, "{:?}", pruning);
+        }
+
+        // Deeper pruning keeps fewer rulers that aren't golomb, but never loses a golomb ruler
+        let depth = |k| RulerQuery::new().order(5).lengths(0, 13).pruning(Pruning::Depth(k)).count();
+        assert!(depth(0) > depth(1) && depth(1) > depth(2));
+        assert_eq!(depth(4), golomb.orders(5, 5).count());
+
+        // Mirror images come in pairs, no golomb ruler of order 3 or more is its own mirror
+        let reduced = golomb.symmetry(true);
+        assert_eq!(reduced.count() * 2, golomb.count());
+        assert!(reduced.iter().all(|r| r.marks <= r.mirror().marks));
+
+        // Lengths below 2 are included
+        assert_eq!(sorted_strings(RulerQuery::new().lengths(0, 1).collect()), vec!["[0, 1]", "[0]"]);
+
+        match golomb.output(OutputMode::Count).run() {
+            QueryOutput::Count(n) => assert_eq!(n, expected.len()),
+            _ => panic!("Expected a count"),
+        }
+
+        // Unbounded queries are lazy
+        let first = RulerQuery::new().order(4).golomb(true).pruning(Pruning::Backtracking).iter().take(3).collect_vec();
+        assert_eq!(first.iter().map(|r| r.to_string()).collect_vec(), vec!["[0, 2, 5, 6]", "[0, 1, 4, 6]", "[0, 4, 6, 7]"]);
+    }
+
+    #[test]
+    fn pruners() {
+
+        use itertools::*;
+
+        let length = 14;
+        let golomb = |order| enumerate_golomb_rulers_with_length(order, length).unwrap();
+
+        // Composition of the built-in strategies
+        let both = PrunerIterator::with_pruner(length, MarkCount(5).and(GolombProperty)).unwrap().collect_vec();
+        assert_eq!(sorted_strings(both), sorted_strings(golomb(5)));
+
+        let either = PrunerIterator::with_pruner(length, MarkCount(4).or(MarkCount(5)).and(GolombProperty)).unwrap().collect_vec();
+        assert_eq!(sorted_strings(either), sorted_strings([golomb(4), golomb(5)].concat()));
+
+        // A subtree kept by one side of `or` is still checked by the other side further down
+        for length in 2..=12 {
+            let union = PrunerIterator::with_pruner(length, GolombProperty.or(MarkCount(4))).unwrap().collect_vec();
+            let expected = PrunerIterator::with_pruner(length, NoPruning).unwrap().filter(|r| r.is_golomb_ruler() || r.order() == 4).collect_vec();
+            assert_eq!(sorted_strings(union), sorted_strings(expected));
+        }
+
+        // A custom pruner skipping every ruler with an odd mark
+        #[derive(Debug)]
+        struct EvenMarks;
+
+        impl Pruner for EvenMarks {
+            fn check(&self, partial: &PartialRuler) -> Verdict {
+                let placed = partial.marks.iter().filter(|m| **m < partial.length as GInt);
+                if placed.clone().any(|m| m % 2 == 1) { Verdict::PruneSubtree } else { Verdict::Keep }
+            }
+        }
+
+        let query = RulerQuery::new().orders(3, 6).lengths(2, 20).golomb(true).pruning(Pruning::Backtracking);
+        let expected = query.iter().filter(|r| r.marks[..r.marks.len() - 1].iter().all(|m| m % 2 == 0)).collect_vec();
+        assert_eq!(sorted_strings(query.with_pruner(EvenMarks).collect()), sorted_strings(expected));
+    }
+
+    #[test]
+    fn dedup() {
+
+        use itertools::*;
+        use crate::{Canonical, dedup_rulers};
+
+        let ruler = Ruler::new(&[1, 3, 7]);
+        assert_eq!(ruler.mirror().to_string(), "[0, 4, 6, 7]");
+        assert_eq!(ruler.canonical(), ruler);
+        assert_eq!(ruler.mirror().canonical(), ruler);
+        assert_eq!(Canonical(ruler.clone()), Canonical(ruler.mirror()));
+        assert_ne!(ruler, ruler.mirror());
+
+        // Merging the results of two enumerators
+        let golomb = enumerate_golomb_rulers(4, 12).unwrap();
+        let merged = golomb.iter().chain(&enumerate_golomb_rulers_pruned(4, 12).unwrap()).cloned().collect_vec();
+
+        assert_eq!(dedup_rulers(merged.clone(), false).collect_vec(), golomb);
+
+        // The first of every ruler and its mirror is kept
+        let reduced = dedup_rulers(merged, true).collect_vec();
+        let canonical = golomb.iter().map(|r| r.canonical()).sorted().dedup().collect_vec();
+        assert_eq!(reduced.iter().map(|r| r.canonical()).sorted().collect_vec(), canonical);
+        assert!(reduced.iter().all(|r| golomb.iter().position(|g| g == r) < golomb.iter().position(|g| *g == r.mirror())
+            || *r == r.mirror()));
+
+        // Streams are deduplicated lazily
+        let stream = (0..).map(|i| Ruler::new(&[[1, 3, 7], [4, 6, 7]][i % 2]));
+        assert_eq!(dedup_rulers(stream.clone(), false).take(2).count(), 2);
+        assert_eq!(dedup_rulers(stream, true).take(1).collect_vec(), vec![ruler]);
+    }
+
+    #[test]
+    fn max_order() {
+
+        use itertools::*;
+
+        // Lengths of the optimal golomb rulers of order 2 to 7
+        let table = max_order_table(30);
+        for (order, ogr) in [(2, 1), (3, 3), (4, 6), (5, 11), (6, 17), (7, 25)] {
+            assert_eq!(table[ogr], order);
+            assert_eq!(table[ogr - 1], order - 1);
+        }
+
+        for length in [6, 11, 12, 14] {
+            let order = table[length];
+            let expected = enumerate_golomb_rulers(order, length).unwrap().iter().map(|r| r.to_string()).sorted().collect_vec();
+            let found = max_order_rulers(length).iter().map(|r| r.to_string()).sorted().collect_vec();
+            assert_eq!(found, expected);
+        }
+
+        // Both optimal golomb rulers of order 5, up to mirroring
+        let canonical = max_order_rulers(11).iter().map(|r| r.canonical().to_string()).sorted().dedup().collect_vec();
+        assert_eq!(canonical, vec!["[0, 1, 4, 9, 11]", "[0, 2, 7, 8, 11]"]);
+    }
+
+    #[test]
+    fn maximal() {
+
+        use itertools::*;
+
+        assert!(GolombRuler::from_marks(&[1, 3]).unwrap().is_maximal());
+        assert!(GolombRuler::from_marks(&[1, 4]).unwrap().is_maximal());
+        assert!(!GolombRuler::from_marks(&[4]).unwrap().is_maximal());
+        assert!(GolombRuler::from_marks(&[1, 2]).is_err());
+
+        for length in 2..16 {
+            let expected = (2..length + 2)
+                .flat_map(|order| enumerate_golomb_rulers_with_length(order, length).unwrap())
+                .filter(|r| r.is_maximal())
+                .collect_vec();
+
+            let maximal = enumerate_maximal_golomb_rulers(length, None, None);
+            assert_eq!(maximal, expected);
+
+            let counts = count_maximal_golomb_rulers(length);
+            assert_eq!(counts.values().sum::<usize>(), expected.len());
+            for (order, count) in counts {
+                assert_eq!(enumerate_maximal_golomb_rulers(length, Some(order), Some(order)).len(), count);
+            }
+        }
+    }
+
+    #[test]
+    fn extensions() {
+
+        use crate::ExtensionError;
+
+        let ruler = GolombRuler::from_marks(&[1, 4, 9]).unwrap();
+        let brute = |max_length: GInt| (1..=max_length).filter(|p| {
+            let mut marks = ruler.marks.clone();
+            marks.push(*p);
+            marks.sort();
+            marks.dedup();
+            marks.len() > ruler.marks.len() && Ruler::new(&marks).is_golomb_ruler()
+        }).collect::<Vec<GInt>>();
+
+        assert_eq!(ruler.valid_extensions(11), vec![11]);
+        assert_eq!(ruler.valid_extensions(30), brute(30));
+        assert!(GolombRuler::from_marks(&[1, 3]).unwrap().valid_extensions(3).is_empty());
+
+        assert_eq!(ruler.with_mark(11).unwrap().to_string(), "[0, 1, 4, 9, 11]");
+        assert!(matches!(ruler.with_mark(4), Err(ExtensionError::DuplicateMark(4))));
+        assert!(matches!(ruler.with_mark(-1), Err(ExtensionError::NegativeMark(-1))));
+
+        // 6 - 1 repeats 9 - 4
+        match ruler.with_mark(6) {
+            Err(ExtensionError::DistanceClash { mark: 6, first, second }) => {
+                assert_eq!((first.lhs, first.rhs, first.dist), (4, 9, 5));
+                assert_eq!((second.lhs, second.rhs, second.dist), (1, 6, 5));
+            },
+            other => panic!("Expected a clash, got {:?}", other),
+        }
+
+        // 2 is right between 0 and 4
+        assert!(matches!(ruler.with_mark(2), Err(ExtensionError::DistanceClash { mark: 2, .. })));
+
+        assert_eq!(ruler.without_mark(4).unwrap().to_string(), "[0, 1, 9]");
+        assert_eq!(ruler.without_mark(9).unwrap().to_string(), "[0, 1, 4]");
+        assert_eq!(ruler.without_mark(0).unwrap().to_string(), "[0, 3, 8]");
+        assert!(matches!(ruler.without_mark(5), Err(ExtensionError::MissingMark(5))));
+        assert!(matches!(GolombRuler::default().without_mark(0), Err(ExtensionError::LastMark)));
+    }
+
+    #[test]
+    fn distance_set() {
+
+        use crate::DistanceSet;
+
+        let mut set = DistanceSet::from(&Ruler::new(&[1, 4, 9]));
+        assert!(set.is_golomb());
+        assert_eq!(set.count(4), 1);
+
+        // 6 repeats the distances 5 (1 -> 6, 4 -> 9) and 3 (1 -> 4, 6 -> 9)
+        assert!(set.add_mark(6));
+        assert!(!set.add_mark(6));
+        assert_eq!(set.collision_count(), 2);
+        assert_eq!(set.count(5), 2);
+        assert!(!set.is_golomb());
+
+        assert!(set.remove_mark(6));
+        assert!(!set.remove_mark(6));
+        assert!(set.is_golomb());
+        assert_eq!(set.add_mark(11), GolombRuler::from_marks(&[1, 4, 9]).unwrap().with_mark(11).is_ok());
+        assert_eq!(set.to_ruler().to_string(), "[0, 1, 4, 9, 11]");
+
+        // Removing marks in any order brings us back to the empty set
+        for m in [4, 0, 11, 1, 9] {
+            set.remove_mark(m);
+        }
+        assert_eq!(set.collision_count(), 0);
+        assert!(set.counts().iter().all(|c| *c == 0));
+
+        // Sets with the same marks are equal, however they were built
+        let mut set = DistanceSet::from_marks(&[0, 5]);
+        set.remove_mark(5);
+        assert_eq!(set, DistanceSet::from_marks(&[0]));
+        assert_ne!(set, DistanceSet::from_marks(&[0, 5]));
+
+        // Agrees with the golomb property of every ruler
+        for ruler in Ruler::from_ids(0, 1 << 10) {
+            let set = DistanceSet::from(&ruler);
+            assert_eq!(set.is_golomb(), ruler.is_golomb_ruler());
+            assert_eq!(Ruler::from(&set), ruler);
+        }
+
+        assert!(!Ruler::new(&[1, 1]).is_golomb_ruler());
+
+        // Huge marks are counted without allocating a vector as long as the ruler, nor truncating distances
+        let huge: GInt = 1 << 44;
+        assert!(Ruler::from_marks(&[0, 1, huge]).unwrap().is_golomb_ruler());
+        assert!(Ruler::from_marks(&[0, 1, (1 << 64) + 2]).unwrap().is_golomb_ruler());
+        assert!(!Ruler::from_marks(&[1, huge, 2 * huge - 1]).unwrap().is_golomb_ruler());
+        assert!(GolombRuler::from_marks(&[0, 1, huge]).is_ok());
+        assert_eq!(Ruler::from_marks(&[huge, 2 * huge]).unwrap().minimal_repair().len(), 1);
+
+        let mut set = DistanceSet::from_marks(&[0, huge, 2 * huge]);
+        assert_eq!(set.count(huge), 2);
+        assert!(set.remove_mark(2 * huge));
+        assert!(set.is_golomb());
+        assert_eq!(set.multiplicities().collect::<Vec<_>>(), vec![(huge, 1)]);
+    }
+
+    #[test]
+    fn collisions() {
+
+        use itertools::*;
+
+        let ruler = Ruler::new(&[1, 2, 3]);
+        let collisions = ruler.collisions();
+        assert_eq!(collisions.len(), 2);
+        assert_eq!((collisions[0].distance, collisions[0].pairs.clone()), (1, vec![(0, 1), (1, 2), (2, 3)]));
+        assert_eq!((collisions[1].distance, collisions[1].pairs.clone()), (2, vec![(0, 2), (1, 3)]));
+        assert_eq!(ruler.collision_count(), 3);
+        assert_eq!(ruler.minimal_repair().len(), 1);
+
+        let golomb = Ruler::new(&[1, 4, 9, 11]);
+        assert!(golomb.collisions().is_empty());
+        assert_eq!(golomb.collision_count(), 0);
+        assert!(golomb.minimal_repair().is_empty());
+
+        // Repairs are as small as possible
+        for ruler in Ruler::from_ids(1 << 8, 1 << 9) {
+
+            let repair = ruler.minimal_repair();
+            let marks = [vec![0], ruler.marks.clone()].concat();
+            let smallest = (0..=marks.len())
+                .find(|k| marks.iter().combinations(marks.len() - k).any(|kept| {
+                    crate::DistanceSet::from_marks(&kept.into_iter().copied().collect_vec()).is_golomb()
+                }))
+                .unwrap();
+
+            assert_eq!(repair.len(), smallest, "{}", ruler);
+            let kept = marks.iter().filter(|m| !repair.contains(m)).copied().collect_vec();
+            assert!(crate::DistanceSet::from_marks(&kept).is_golomb(), "{}", ruler);
+        }
+    }
+
+    #[test]
+    fn near_golomb() {
+
+        let order = 5;
+        let max_length = 14;
+
+        assert!(Ruler::new(&[1, 2]).is_near_golomb(2));
+        assert!(!Ruler::new(&[1, 2, 3]).is_near_golomb(2));
+        assert!(Ruler::new(&[1, 4, 9, 11]).is_near_golomb(1));
+
+        assert_eq!(enumerate_near_golomb_rulers(order, max_length, Some(1), None).unwrap(), enumerate_golomb_rulers(order, max_length).unwrap());
+        assert_eq!(enumerate_near_golomb_rulers(order, max_length, None, Some(0)).unwrap(), enumerate_golomb_rulers(order, max_length).unwrap());
+        assert_eq!(enumerate_near_golomb_rulers(order, max_length, None, None).unwrap(), enumerate_golomb_rulers(order, max_length).unwrap());
+
+        let all = enumerate_rulers_with_order(order, max_length).unwrap();
+        for lambda_ in 2..4 {
+            let expected: Vec<Ruler> = all.iter().filter(|r| r.is_near_golomb(lambda_)).cloned().collect();
+            assert_eq!(enumerate_near_golomb_rulers(order, max_length, Some(lambda_), None).unwrap(), expected);
+        }
+
+        for max_collisions in 1..4 {
+            let expected: Vec<Ruler> = all.iter().filter(|r| r.collision_count() <= max_collisions).cloned().collect();
+            assert_eq!(enumerate_near_golomb_rulers(order, max_length, None, Some(max_collisions)).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn turnpike() {
+
+        use itertools::*;
+        use crate::{OgrError, reconstruct_rulers, reconstruct_beltway};
+
+        let pairwise = |marks: &[GInt]| marks.iter().tuple_combinations().map(|(a, b)| b - a).collect_vec();
+
+        let ruler = Ruler::new(&[1, 4, 9, 11]);
+        assert_eq!(reconstruct_rulers(pairwise(&[0, 1, 4, 9, 11])).unwrap(), vec![ruler.canonical()]);
+
+        // Two golomb rulers with the same distances
+        let bloom = reconstruct_rulers(pairwise(&[0, 1, 4, 10, 12, 17])).unwrap();
+        assert_eq!(bloom.iter().map(|r| r.to_string()).collect_vec(), vec!["[0, 1, 4, 10, 12, 17]", "[0, 1, 8, 11, 13, 17]"]);
+
+        // Every solution has the same distances, and the original ruler is one of them
+        for ruler in Ruler::from_ids(1 << 7, 1 << 8).into_iter().step_by(3) {
+            let marks = [vec![0], ruler.marks.clone()].concat();
+            let distances = pairwise(&marks).into_iter().sorted().collect_vec();
+            let solutions = reconstruct_rulers(distances.clone()).unwrap();
+
+            assert!(solutions.contains(&ruler.canonical()), "{}", ruler);
+            for s in solutions {
+                let marks = [vec![0], s.marks.clone()].concat();
+                assert_eq!(pairwise(&marks).into_iter().sorted().collect_vec(), distances);
+            }
+        }
+
+        assert!(reconstruct_rulers(vec![1, 2]).unwrap().is_empty());
+        assert!(reconstruct_rulers(vec![1, 1, 1]).unwrap().is_empty());
+
+        // Perfect difference sets use every cyclic distance once
+        assert_eq!(reconstruct_beltway((1..7).collect(), 7).unwrap(), vec![vec![0, 1, 3]]);
+        assert_eq!(reconstruct_beltway((1..13).collect(), 13).unwrap(), vec![vec![0, 1, 3, 9], vec![0, 1, 4, 6]]);
+        assert!(reconstruct_beltway(vec![1, 2, 3, 4, 5, 6], 8).unwrap().is_empty());
+
+        // Invalid inputs are errors rather than panics or huge allocations
+        assert!(matches!(reconstruct_rulers(vec![-3]), Err(OgrError::InvalidMarks { .. })));
+        assert!(matches!(reconstruct_rulers(vec![-1]), Err(OgrError::InvalidMarks { .. })));
+        assert!(matches!(reconstruct_rulers(vec![0, 1, 1]), Err(OgrError::InvalidMarks { .. })));
+        assert_eq!(reconstruct_rulers(vec![1 << 100]).unwrap(), vec![Ruler::new(&[1 << 100])]);
+        assert!(matches!(reconstruct_beltway(vec![], 0), Err(OgrError::InvalidArgument { .. })));
+        let err = reconstruct_beltway(vec![], -4).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid circumference: the circumference of a circle must be positive, got -4");
+        assert!(matches!(reconstruct_beltway(vec![-1, 8], 7), Err(OgrError::InvalidMarks { .. })));
+        assert_eq!(reconstruct_beltway(vec![1 << 100, 1], (1 << 100) + 1).unwrap(), vec![vec![0, 1]]);
+    }
+
+    #[test]
+    fn sidon() {
+
+        use itertools::*;
+        use crate::{OgrError, SidonSequence, StepRule};
+
+        let mian_chowla = SidonSequence::mian_chowla().take(12).collect_vec();
+        assert_eq!(mian_chowla, vec![1, 2, 4, 8, 13, 21, 31, 45, 66, 81, 97, 123]);
+
+        // Greedy extension of [0, 1, 3]
+        let improved = crate::generate_golomb_ruler_improved(10).unwrap();
+        assert_eq!(SidonSequence::new(&[0, 1, 3], StepRule::Smallest, None).unwrap().take(10).collect_vec(), improved);
+
+        let rules = [StepRule::Smallest, StepRule::LargestGap(5), StepRule::Random { window: 20, seed: 7 }];
+        for rule in rules {
+            let marks = SidonSequence::new(&[0, 5], rule, None).unwrap().take(10).collect_vec();
+            assert_eq!(&marks[..2], &[0, 5]);
+            assert!(marks.windows(2).all(|w| w[0] < w[1]));
+            assert!(crate::DistanceSet::from_marks(&marks).is_golomb(), "{:?}", rule);
+        }
+
+        // Random steps are reproducible
+        let random = |seed| SidonSequence::new(&[0], StepRule::Random { window: 30, seed }, None).unwrap().take(8).collect_vec();
+        assert_eq!(random(3), random(3));
+
+        // Bounded sequences end
+        assert_eq!(SidonSequence::new(&[1], StepRule::Smallest, Some(30)).unwrap().collect_vec(), vec![1, 2, 4, 8, 13, 21]);
+        assert!(matches!(SidonSequence::new(&[1, 40], StepRule::Smallest, Some(30)), Err(OgrError::InvalidMarks { .. })));
+
+        // Rules only pick among the marks up to the bound: after [0, 1, 3], 4, 5 and 6 repeat a distance
+        let capped = SidonSequence::new(&[0, 1, 3], StepRule::LargestGap(20), Some(7)).unwrap().collect_vec();
+        assert_eq!(capped, vec![0, 1, 3, 7]);
+        for rule in rules {
+            let marks = SidonSequence::new(&[0], rule, Some(50)).unwrap().collect_vec();
+            let ruler = GolombRuler::from_marks(&marks).unwrap();
+            assert!(ruler.length() <= 50);
+            assert!((ruler.length() + 1..=50).all(|p| ruler.with_mark(p).is_err()), "{:?}", rule);
+        }
+        assert!(SidonSequence::new(&[0, 1, 2], StepRule::Smallest, None).is_err());
+        for rule in [StepRule::LargestGap(0), StepRule::LargestGap(-3), StepRule::Random { window: 0, seed: 1 }] {
+            assert!(matches!(SidonSequence::new(&[0, 1], rule, None), Err(OgrError::InvalidArgument { .. })), "{:?}", rule);
+        }
+    }
+
+    #[test]
+    fn beam_search() {
+
+        use crate::BeamSearch;
+
+        let search = BeamSearch::new().width(32);
+        for order in 2..=10 {
+            let ruler = search.run(order).unwrap();
+            assert_eq!(ruler.order(), order);
+            assert!(ruler.is_golomb_ruler());
+        }
+
+        // Optimal for small orders, and no worse than the greedy construction
+        assert_eq!(search.run(5).unwrap().length(), 11);
+        assert!(search.run(10).unwrap().length() <= *crate::generate_golomb_ruler_improved(10).unwrap().last().unwrap());
+
+        // Reproducible for a given seed
+        assert_eq!(search.seed(4).run(9).unwrap(), search.seed(4).run(9).unwrap());
+
+        // Custom scores, here plain greedy on the last mark
+        let greedy = BeamSearch::new().width(1).score(|distances, _| *distances.marks().last().unwrap() as f64);
+        assert_eq!(greedy.run(6).unwrap(), Ruler::new(&[1, 3, 7, 12, 20]));
+
+        // Long rulers complete with the default settings, widening the horizon as they grow
+        for order in [20, 24] {
+            let ruler = BeamSearch::new().run(order).unwrap();
+            assert_eq!(ruler.order(), order);
+            assert!(ruler.length() <= *crate::generate_golomb_ruler_improved(order as u32).unwrap().last().unwrap());
+        }
+        assert_eq!(BeamSearch::new().width(1).run(40).unwrap().order(), 40);
+        assert_eq!(BeamSearch::new().horizon(0).run(8).unwrap().order(), 8);
+    }
+
+    #[test]
+    fn meet_in_the_middle() {
+
+        use itertools::*;
+
+        for (order, length) in [(2, 5), (3, 6), (4, 9), (5, 13), (6, 19), (7, 27)] {
+            let mut expected = enumerate_golomb_rulers_pruned_with_length(order, length).unwrap().into_iter().filter(|r| r.is_golomb_ruler()).collect_vec();
+            expected.sort();
+
+            let (rulers, stats) = super::meet_in_the_middle(order, length).unwrap();
+            assert_eq!(rulers, expected, "order {}, length {}", order, length);
+            assert_eq!(stats.rulers, rulers.len());
+            assert!(stats.memory > 0);
+        }
+    }
+
+    #[test]
+    fn estimate_tree_size() {
+
+        use std::time::Duration;
+
+        for (order, max_length) in [(4, 12), (5, 16), (6, 20)] {
+
+            // Small enough for the timed sample to visit the whole tree
+            let estimate = super::estimate_golomb_rulers_pruned(order, max_length, 2000, 1, Duration::from_secs(60)).unwrap();
+            assert!(estimate.complete);
+
+            let exact = estimate.sampled_nodes as f64;
+            assert!(estimate.low <= estimate.nodes && estimate.nodes <= estimate.high);
+            assert!((estimate.nodes - exact).abs() < 0.1 * exact, "{:?}, exact {}", estimate, exact);
+        }
+
+        // Reproducible for a given seed
+        let nodes = |seed| super::estimate_golomb_rulers_pruned(6, 30, 100, seed, Duration::ZERO).unwrap().nodes;
+        assert_eq!(nodes(3), nodes(3));
+    }
+
+    #[test]
+    fn search_tree() {
+
+        use super::{record_search_tree, NodeOutcome, MAX_TREE_LENGTH};
+        use crate::OgrError;
+
+        // Without backtracking, leaves are checked for the golomb property
+        for pruning in [Pruning::MarkCount, Pruning::Backtracking] {
+            let tree = RulerQuery::new().order(4).lengths(0, 9).golomb(true).pruning(pruning).search_tree().unwrap();
+            let yielded: Vec<Ruler> = tree.nodes.iter()
+                .filter(|n| n.outcome == NodeOutcome::Yielded)
+                .map(|n| n.state.to_ruler())
+                .collect();
+            assert_eq!(yielded, enumerate_golomb_rulers(4, 9).unwrap());
+
+            // Backtracking only prunes repeated distances before reaching the leaves
+            assert!(tree.nodes.iter().any(|n| n.outcome == NodeOutcome::PrunedByOrder));
+            assert!(tree.nodes.iter().any(|n| n.outcome == NodeOutcome::PrunedByGolomb));
+        }
+
+        let tree = RulerQuery::new().order(3).lengths(0, 4).golomb(true).pruning(Pruning::MarkCount).search_tree().unwrap();
+        let dot = tree.to_dot();
+        assert!(dot.starts_with("digraph"));
+        assert!(dot.contains("L4_10 -> L4_100 [label=\"0\"]"));
+        assert!(dot.contains("L4_100 [label=\"100\", fillcolor=palegreen]"));
+        assert!(dot.contains("L4_010 [label=\"010\", fillcolor=tomato]"));
+        assert!(tree.to_json().contains("{\"id\": \"L3_\", \"parent\": null, \"length\": 3, \"state\": \"\", \"outcome\": \"explored\"}"));
+
+        // Any pruner, and queries with several orders, yield every ruler they accept
+        let tree = record_search_tree(MaxCollisions(0), 7).unwrap();
+        let yielded = tree.nodes.iter().filter(|n| n.outcome == NodeOutcome::Yielded).count();
+        assert_eq!(yielded, RulerQuery::new().lengths(2, 7).golomb(true).count());
+
+        let query = RulerQuery::new().orders(3, 4).lengths(0, 8).pruning(Pruning::Backtracking);
+        let tree = query.search_tree().unwrap();
+        let yielded = tree.nodes.iter().filter(|n| n.outcome == NodeOutcome::Yielded).count();
+        assert_eq!(yielded, query.count());
+
+        // Trees grow too fast past our maximum length
+        assert!(matches!(record_search_tree(NoPruning, MAX_TREE_LENGTH + 1), Err(OgrError::InvalidLength { .. })));
+        assert!(matches!(RulerQuery::new().lengths(0, 21).search_tree(), Err(OgrError::InvalidLength { .. })));
+        assert!(RulerQuery::new().search_tree().is_err());
+    }
+
+    #[test]
+    fn notation() {
+
+        use crate::{RulerFormat, ParseRulerError};
+
+        let ruler = GolombRuler::from_marks(&[1, 4, 9, 11]).unwrap();
+        for text in ["0 1 4 9 11", "[0, 1, 4, 9, 11]", "1-3-5-2", " 1 - 3 - 5 - 2 ", "11,9 4,1 0", "[5, 6, 9, 14, 16]", "-3 -2 1 6 8"] {
+            assert_eq!(text.parse::<GolombRuler>(), Ok(ruler.clone()), "{}", text);
+        }
+
+        assert_eq!(ruler.format(RulerFormat::Marks), "0 1 4 9 11");
+        assert_eq!(ruler.format(RulerFormat::Brackets), "[0, 1, 4, 9, 11]");
+        assert_eq!(ruler.format(RulerFormat::Gaps), "1-3-5-2");
+        for format in [RulerFormat::Marks, RulerFormat::Brackets, RulerFormat::Gaps] {
+            assert_eq!(ruler.format(format).parse::<GolombRuler>(), Ok(ruler.clone()));
+        }
+
+        // Every notation reads back its own output
+        for order in 1..=3 {
+            for ruler in RulerQuery::new().order(order).lengths(0, 8).iter_golomb() {
+                for format in [RulerFormat::Marks, RulerFormat::Brackets, RulerFormat::Gaps] {
+                    let text = ruler.format(format);
+                    assert_eq!(GolombRuler::parse_as(&text, format), Ok(ruler.clone()), "{:?}", text);
+                    assert_eq!(Ruler::parse_as(&text, format), Ok(ruler.as_ruler().clone()), "{:?}", text);
+                    if format != RulerFormat::Gaps || order > 2 {
+                        assert_eq!(text.parse::<GolombRuler>(), Ok(ruler.clone()), "{:?}", text);
+                    }
+                }
+            }
+        }
+
+        // A single gap is a mark unless the format says otherwise, and signs aren't gaps
+        assert_eq!("11".parse::<Ruler>(), Ok(Ruler::default()));
+        assert_eq!(Ruler::parse_as("11", RulerFormat::Gaps), Ok(Ruler::new(&[11])));
+        assert_eq!("3 -2".parse::<Ruler>(), Ok(Ruler::new(&[5])));
+        assert_eq!("[3,-2, 0]".parse::<Ruler>(), Ok(Ruler::new(&[2, 5])));
+        assert_eq!(Ruler::parse_as("1-3", RulerFormat::Marks), Err(ParseRulerError::InvalidNumber("1-3".to_string())));
+        assert_eq!("1-x".parse::<Ruler>(), Err(ParseRulerError::InvalidNumber("x".to_string())));
+
+        assert_eq!("0".parse::<GolombRuler>(), Ok(GolombRuler::default()));
+        assert_eq!("[]".parse::<GolombRuler>(), Err(ParseRulerError::Empty));
+        assert_eq!("0 1 x".parse::<GolombRuler>(), Err(ParseRulerError::InvalidNumber("x".to_string())));
+        assert_eq!("1-0-2".parse::<GolombRuler>(), Err(ParseRulerError::InvalidGap(0)));
+        assert_eq!(Ruler::parse_as(&format!("{}-1", GInt::MAX), RulerFormat::Gaps), Err(ParseRulerError::GapOverflow));
+        assert_eq!("0 1 1 3".parse::<GolombRuler>(), Err(ParseRulerError::DuplicateMark(1)));
+        assert_eq!("0 1 2".parse::<GolombRuler>(), Err(ParseRulerError::NotGolomb(vec![0, 1, 2])));
+        assert_eq!("0 1 2".parse::<Ruler>(), Ok(Ruler::new(&[1, 2])));
+    }
+
+    #[test]
+    fn from_marks() {
+
+        use crate::ExtensionError;
+
+        let ruler = Ruler::new(&[1, 4, 9, 11]);
+        assert_eq!(GolombRuler::from_marks(&[0, 1, 4, 9, 11]).unwrap(), ruler);
+        assert_eq!(GolombRuler::from_marks(&[11, 4, 1, 9]).unwrap(), ruler);
+        assert_eq!(GolombRuler::from_marks(&[]).unwrap(), Ruler::new(&[]));
+        assert_eq!(ruler.to_list(), vec![0, 1, 4, 9, 11]);
+
+        assert!(matches!(GolombRuler::from_marks(&[0, -1, 3]), Err(ExtensionError::NegativeMark(-1))));
+        assert!(matches!(GolombRuler::from_marks(&[1, 3, 3]), Err(ExtensionError::DuplicateMark(3))));
+        assert!(matches!(GolombRuler::from_marks(&[1, 2]), Err(ExtensionError::NotGolomb)));
+    }
+
+    #[test]
+    fn ruler_types() {
+
+        use crate::ExtensionError;
+
+        let ruler = Ruler::from_marks(&[2, 0, 1]).unwrap();
+        assert_eq!(ruler.to_list(), vec![0, 1, 2]);
+        assert!(matches!(GolombRuler::try_from(ruler.clone()), Err(ExtensionError::NotGolomb)));
+        assert!(matches!(Ruler::from_marks(&[1, 1]), Err(ExtensionError::DuplicateMark(1))));
+
+        let golomb = GolombRuler::try_from(Ruler::new(&[1, 3])).unwrap();
+        assert_eq!(Ruler::from(golomb.clone()), Ruler::new(&[1, 3]));
+        assert_eq!(golomb.mirror(), GolombRuler::from_marks(&[2, 3]).unwrap());
+
+        // General enumerations keep rulers without the golomb property, golomb enumerations never do
+        assert!(enumerate_rulers_with_order(3, 6).unwrap().iter().any(|r| !r.is_golomb_ruler()));
+        let depth = enumerate_golomb_rulers_depth_with_length(4, 9, 1).unwrap();
+        assert_eq!(depth, enumerate_golomb_rulers_with_length(4, 9).unwrap());
+        assert_eq!(enumerate_golomb_rulers_pruned_with_length(4, 9).unwrap(), depth);
+    }
+
+    #[test]
+    fn difference_triangle() {
+
+        use crate::Distance;
+        use itertools::*;
+
+        let ruler = Ruler::new(&[1, 4, 9, 11]);
+        assert_eq!(ruler.difference_triangle(), vec![vec![1, 3, 5, 2], vec![4, 8, 7], vec![9, 10], vec![11]]);
+        assert_eq!(ruler.format_triangle(), "   1   3   5   2\n     4   8   7\n       9  10\n        11");
+        assert!(Ruler::default().difference_triangle().is_empty());
+
+        // Every pair of marks appears once, 0 included
+        let distances = ruler.distances();
+        assert_eq!(distances.len(), 10);
+        assert_eq!(distances[..5], [(0, 1), (0, 4), (0, 9), (0, 11), (1, 4)].map(|(lhs, rhs)| Distance { lhs, rhs, dist: rhs - lhs }));
+
+        let mut flat = ruler.difference_triangle().concat();
+        flat.sort();
+        assert_eq!(flat, distances.iter().map(|d| d.dist).sorted().collect_vec());
+        assert_eq!(ruler.pairs_by_distance()[&8], vec![(1, 9)]);
+        assert_eq!(Ruler::new(&[1, 2]).pairs_by_distance()[&1], vec![(0, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn errors() {
+
+        use crate::OgrError;
+
+        assert!(matches!(enumerate_golomb_rulers(0, 5), Err(OgrError::InvalidOrder { order: 0, .. })));
+        assert!(matches!(enumerate_near_golomb_rulers(0, 5, Some(1), None), Err(OgrError::InvalidOrder { .. })));
+        assert!(matches!(PrunerIterator::with_pruner(1, NoPruning), Err(OgrError::InvalidLength { length: 1, .. })));
+        assert!(matches!(enumerate_golomb_rulers_with_prefix(vec![0, 2, 1], 4, 9), Err(OgrError::InvalidMarks { .. })));
+        assert!(matches!(crate::generate_golomb_ruler_improved(0), Err(OgrError::InvalidOrder { .. })));
+        assert!(matches!(Ruler::new(&[200]).to_id(), Err(OgrError::Overflow(_))));
+
+        // Degenerate rulers are golomb rulers rather than panics
+        assert!(Ruler::default().is_golomb_ruler_order_1());
+        assert!(Ruler::new(&[1]).is_golomb_ruler_order_1());
+    }
+}
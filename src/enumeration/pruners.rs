@@ -0,0 +1,542 @@
+//! Pluggable pruning strategies for the tree of ruler states.
+//!
+//! While traversing the rulers of a given length, every time a position of the state is decided
+//! the [`Pruner`] is shown the partial ruler built so far and decides whether to keep exploring,
+//! to skip the entire subtree, or (at a leaf) to reject the ruler. Every enumeration strategy of this
+//! crate is a pruner, and pruners compose with [`Pruner::and`] and [`Pruner::or`].
+
+use std::iter::once;
+use std::sync::Arc;
+#[cfg(feature = "python")]
+use std::sync::Mutex;
+
+#[cfg(feature = "python")]
+use pyo3::exceptions;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+use crate::{GInt, DistanceSet};
+
+/// The decision a [`Pruner`] takes on a node of the tree
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verdict {
+    /// Keep exploring, and accept the ruler if this is a leaf
+    Keep,
+    /// Don't accept this leaf. Treated as `Keep` on inner nodes
+    RejectLeaf,
+    /// Skip every ruler below this node
+    PruneSubtree,
+}
+
+/// A view of the partial ruler at a node of the tree.
+#[derive(Debug)]
+pub struct PartialRuler<'a> {
+    /// Marks placed so far, starting at 0. At a leaf, the final mark `length` is included
+    pub marks: &'a [GInt],
+    /// Number of times each distance appears between the placed marks and the final mark `length`, indexed by distance.
+    /// Empty if the pruner doesn't [need distances](Pruner::needs_distances)
+    pub distances: &'a [usize],
+    /// Number of pairs of marks, the final mark included, repeating the distance of another pair.
+    /// Zero if the pruner doesn't [need distances](Pruner::needs_distances)
+    pub collisions: usize,
+    /// Number of positions of the state that have been decided
+    pub depth: usize,
+    /// Length of every ruler in the tree
+    pub length: usize,
+    /// Whether every position has been decided
+    pub is_leaf: bool,
+}
+
+impl PartialRuler<'_> {
+
+    /// Number of marks of this ruler so far, including the final mark `length`
+    pub fn total_marks(&self) -> usize {
+        if self.is_leaf { self.marks.len() } else { self.marks.len() + 1 }
+    }
+
+    /// Number of positions that are left to decide
+    pub fn remaining(&self) -> usize {
+        self.length - 1 - self.depth
+    }
+
+    /// The mark that was placed at this node, if the position at `depth` is a mark
+    pub fn new_mark(&self) -> Option<GInt> {
+        match self.marks.last() {
+            Some(m) if !self.is_leaf && self.depth > 0 && *m == self.depth as GInt => Some(*m),
+            _ => None
+        }
+    }
+
+    /// The largest mark placed so far, not counting the final mark
+    pub fn last_mark(&self) -> GInt {
+        let n = self.marks.len();
+        if self.is_leaf { self.marks[n - 2] } else { self.marks[n - 1] }
+    }
+
+    /// The distances introduced by the mark placed at this node, including its distance to `length`
+    pub fn new_distances(&self) -> impl Iterator<Item = GInt> + '_ {
+        let placed = self.new_mark().map(|m| {
+            let previous = &self.marks[0..self.marks.len() - 1];
+            previous.iter().map(move |p| m - p).chain(once(self.length as GInt - m))
+        });
+        placed.into_iter().flatten()
+    }
+}
+
+/// A strategy deciding which subtrees of ruler states to explore.
+pub trait Pruner: Send + Sync + std::fmt::Debug {
+
+    /// Decide what to do with the node `partial`.
+    ///
+    /// The verdict must hold for the whole partial ruler, not only for what changed since the parent node:
+    /// [`Pruner::or`] visits subtrees that one of its pruners would have skipped, and asks it again further down.
+    fn check(&self, partial: &PartialRuler) -> Verdict;
+
+    /// Whether `check` reads the `distances` and `collisions` of the partial ruler.
+    ///
+    /// Keeping them up to date costs more than most checks on the marks, so pruners that only look at the marks
+    /// should return `false`.
+    fn needs_distances(&self) -> bool {
+        true
+    }
+
+    /// Only keep rulers accepted by both `self` and `other`
+    fn and<Q: Pruner>(self, other: Q) -> And<Self, Q> where Self: Sized {
+        And(self, other)
+    }
+
+    /// Keep rulers accepted by either `self` or `other`
+    fn or<Q: Pruner>(self, other: Q) -> Or<Self, Q> where Self: Sized {
+        Or(self, other)
+    }
+}
+
+impl<P: Pruner + ?Sized> Pruner for Box<P> {
+    fn check(&self, partial: &PartialRuler) -> Verdict {
+        self.as_ref().check(partial)
+    }
+
+    fn needs_distances(&self) -> bool {
+        self.as_ref().needs_distances()
+    }
+}
+
+impl<P: Pruner + ?Sized> Pruner for Arc<P> {
+    fn check(&self, partial: &PartialRuler) -> Verdict {
+        self.as_ref().check(partial)
+    }
+
+    fn needs_distances(&self) -> bool {
+        self.as_ref().needs_distances()
+    }
+}
+
+/// Both pruners must keep a node, so the most severe verdict wins
+#[derive(Clone, Debug)]
+pub struct And<A, B>(pub A, pub B);
+
+impl<A: Pruner, B: Pruner> Pruner for And<A, B> {
+    fn check(&self, partial: &PartialRuler) -> Verdict {
+        match self.0.check(partial) {
+            Verdict::PruneSubtree => Verdict::PruneSubtree,
+            lhs => Verdict::max(lhs, self.1.check(partial)),
+        }
+    }
+
+    fn needs_distances(&self) -> bool {
+        self.0.needs_distances() || self.1.needs_distances()
+    }
+}
+
+/// Either pruner may keep a node, so the least severe verdict wins
+#[derive(Clone, Debug)]
+pub struct Or<A, B>(pub A, pub B);
+
+impl<A: Pruner, B: Pruner> Pruner for Or<A, B> {
+    fn check(&self, partial: &PartialRuler) -> Verdict {
+        match self.0.check(partial) {
+            Verdict::Keep => Verdict::Keep,
+            lhs => Verdict::min(lhs, self.1.check(partial)),
+        }
+    }
+
+    fn needs_distances(&self) -> bool {
+        self.0.needs_distances() || self.1.needs_distances()
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                             Built-in strategies                            */
+/* -------------------------------------------------------------------------- */
+
+/// Visit every ruler
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoPruning;
+
+impl Pruner for NoPruning {
+    fn check(&self, _partial: &PartialRuler) -> Verdict {
+        Verdict::Keep
+    }
+
+    fn needs_distances(&self) -> bool {
+        false
+    }
+}
+
+/// Prune subtrees that have too many marks, or not enough room left to reach `order` marks
+#[derive(Clone, Copy, Debug)]
+pub struct MarkCount(pub usize);
+
+impl Pruner for MarkCount {
+    fn check(&self, partial: &PartialRuler) -> Verdict {
+        let total = partial.total_marks();
+        if total > self.0 || total + partial.remaining() < self.0 {
+            Verdict::PruneSubtree
+        } else {
+            Verdict::Keep
+        }
+    }
+
+    fn needs_distances(&self) -> bool {
+        false
+    }
+}
+
+/// Prune subtrees as soon as the first order of distances repeat,
+/// i.e. when two marks are as far from 0 as from the final mark.
+///
+/// See `GolombRuler::is_golomb_ruler_order_1`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FirstOrderDistances;
+
+impl Pruner for FirstOrderDistances {
+    fn check(&self, partial: &PartialRuler) -> Verdict {
+        let length = partial.length as GInt;
+        let inner = partial.marks.iter().filter(|m| 0 < **m && **m < length);
+        if inner.clone().any(|m| partial.marks.binary_search(&(length - m)).is_ok()) {
+            Verdict::PruneSubtree
+        } else {
+            Verdict::Keep
+        }
+    }
+
+    fn needs_distances(&self) -> bool {
+        false
+    }
+}
+
+/// Prune subtrees as soon as two pairs of marks at most `k` marks apart have the same distance.
+///
+/// Order `j` distances separate marks `j` positions apart, so order 1 distances are the gaps of the ruler.
+/// The final mark only counts at a leaf, where it is the last placed mark.
+#[derive(Clone, Copy, Debug)]
+pub struct DistanceOrders(pub usize);
+
+impl Pruner for DistanceOrders {
+    fn check(&self, partial: &PartialRuler) -> Verdict {
+        let marks = partial.marks;
+        let mut distances: Vec<GInt> = (1..marks.len())
+            .flat_map(|i| (i.saturating_sub(self.0)..i).map(move |j| marks[i] - marks[j]))
+            .collect();
+        let total = distances.len();
+        distances.sort_unstable();
+        distances.dedup();
+
+        if distances.len() < total { Verdict::PruneSubtree } else { Verdict::Keep }
+    }
+
+    fn needs_distances(&self) -> bool {
+        false
+    }
+}
+
+/// Prune subtrees as soon as a distance repeats
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GolombProperty;
+
+impl Pruner for GolombProperty {
+    fn check(&self, partial: &PartialRuler) -> Verdict {
+        if partial.collisions > 0 {
+            Verdict::PruneSubtree
+        } else {
+            Verdict::Keep
+        }
+    }
+}
+
+/// Prune subtrees as soon as a distance appears more than `lambda` times, giving B2[g] sets.
+///
+/// A multiplicity of 1 is the golomb property.
+#[derive(Clone, Copy, Debug)]
+pub struct MaxMultiplicity(pub usize);
+
+impl Pruner for MaxMultiplicity {
+    fn check(&self, partial: &PartialRuler) -> Verdict {
+        if partial.distances.iter().any(|c| *c > self.0) {
+            Verdict::PruneSubtree
+        } else {
+            Verdict::Keep
+        }
+    }
+}
+
+/// Prune subtrees as soon as more than `max_collisions` pairs of marks repeat the distance of another pair
+#[derive(Clone, Copy, Debug)]
+pub struct MaxCollisions(pub usize);
+
+impl Pruner for MaxCollisions {
+    fn check(&self, partial: &PartialRuler) -> Verdict {
+        if partial.collisions > self.0 { Verdict::PruneSubtree } else { Verdict::Keep }
+    }
+}
+
+/// Reject golomb rulers that are not maximal, i.e. where a new mark can be placed without breaking the golomb property.
+///
+/// Only leaves are checked, and they are assumed to be golomb rulers: combine it with [`GolombProperty`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Maximal;
+
+impl Pruner for Maximal {
+    fn check(&self, partial: &PartialRuler) -> Verdict {
+
+        if !partial.is_leaf {
+            return Verdict::Keep
+        }
+
+        let marks = partial.marks;
+        let extends = |p: GInt| {
+            // Distances to every mark are new, and p isn't right in the middle of two marks
+            marks.iter().all(|m| partial.distances[(p - m).unsigned_abs() as usize] == 0)
+                && marks.iter().take_while(|m| **m < p).all(|m| marks.binary_search(&(2 * p - m)).is_err())
+        };
+
+        let length = partial.length as GInt;
+        if (1..length).any(|p| marks.binary_search(&p).is_err() && extends(p)) {
+            Verdict::RejectLeaf
+        } else {
+            Verdict::Keep
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                               Python pruners                               */
+/* -------------------------------------------------------------------------- */
+
+/// Snapshot of a [`PartialRuler`] handed to python pruners
+#[cfg(feature = "python")]
+#[derive(Clone, Debug)]
+#[pyclass(name = "PartialRuler")]
+pub struct PyPartialRuler {
+    #[pyo3(get)]
+    marks: Vec<GInt>,
+    #[pyo3(get)]
+    distances: Vec<usize>,
+    #[pyo3(get)]
+    collisions: usize,
+    #[pyo3(get)]
+    depth: usize,
+    #[pyo3(get)]
+    length: usize,
+    #[pyo3(get)]
+    is_leaf: bool,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PyPartialRuler {
+    fn __repr__(&self) -> String {
+        format!("PartialRuler(marks={:?}, depth={}, length={}, is_leaf={})", self.marks, self.depth, self.length, self.is_leaf)
+    }
+}
+
+/// A pruner calling a python function `func(partial: PartialRuler)`.
+///
+/// The function returns `True` or `"keep"` to keep the node, `"reject"` to reject a leaf, and `False` or
+/// `"prune"` to skip the subtree (at a leaf, `False` rejects it).
+///
+/// # Overhead
+/// Every call acquires the GIL and copies the marks and distance counts into a new python object,
+/// which costs on the order of a microsecond, against a few nanoseconds for the built-in pruners.
+/// As the function is called on every node of the tree, set `leaves_only` when it only needs to see
+/// complete rulers, and combine it with a built-in strategy so that the tree stays small.
+///
+/// The first exception raised by `func` stops the traversal and is kept until [`PyPruner::take_error`].
+#[cfg(feature = "python")]
+#[derive(Debug)]
+pub struct PyPruner {
+    func: PyObject,
+    leaves_only: bool,
+    error: Mutex<Option<PyErr>>,
+}
+
+#[cfg(feature = "python")]
+impl PyPruner {
+
+    pub fn new(func: PyObject, leaves_only: bool) -> Self {
+        PyPruner {
+            func,
+            leaves_only,
+            error: Mutex::new(None),
+        }
+    }
+
+    /// Return the first exception raised by our python function, if any
+    pub fn take_error(&self) -> PyResult<()> {
+        match self.error.lock().unwrap().take() {
+            Some(err) => Err(err),
+            None => Ok(())
+        }
+    }
+
+    fn call(&self, py: Python, partial: &PartialRuler) -> PyResult<Verdict> {
+
+        let snapshot = PyPartialRuler {
+            marks: partial.marks.to_vec(),
+            distances: partial.distances.to_vec(),
+            collisions: partial.collisions,
+            depth: partial.depth,
+            length: partial.length,
+            is_leaf: partial.is_leaf,
+        };
+
+        let result = self.func.call1(py, (snapshot,))?;
+        let result = result.as_ref(py);
+
+        if let Ok(keep) = result.extract::<bool>() {
+            return Ok(match (keep, partial.is_leaf) {
+                (true, _) => Verdict::Keep,
+                (false, true) => Verdict::RejectLeaf,
+                (false, false) => Verdict::PruneSubtree,
+            })
+        }
+
+        match result.extract::<&str>() {
+            Ok("keep") => Ok(Verdict::Keep),
+            Ok("reject") => Ok(Verdict::RejectLeaf),
+            Ok("prune") => Ok(Verdict::PruneSubtree),
+            _ => Err(exceptions::PyTypeError::new_err(format!(
+                "A pruner must return a bool or one of 'keep', 'reject' or 'prune', got {}", result
+            )))
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+impl Pruner for PyPruner {
+    fn check(&self, partial: &PartialRuler) -> Verdict {
+
+        if self.leaves_only && !partial.is_leaf {
+            return Verdict::Keep
+        }
+
+        if self.error.lock().unwrap().is_some() {
+            return Verdict::PruneSubtree
+        }
+
+        Python::with_gil(|py| match self.call(py, partial) {
+            Ok(verdict) => verdict,
+            Err(err) => {
+                self.error.lock().unwrap().get_or_insert(err);
+                Verdict::PruneSubtree
+            }
+        })
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+/*                                  Traversal                                 */
+/* -------------------------------------------------------------------------- */
+
+/// Marks and distance counts of the partial ruler at the current node, updated incrementally as we traverse the tree.
+///
+/// The final mark `length` is always part of `marks` and `distances`, and only shown to pruners at leaves.
+/// Distances are only kept for pruners that [need them](Pruner::needs_distances).
+#[derive(Debug, Default)]
+pub(super) struct PruneScratch {
+    marks: Vec<GInt>,
+    distances: DistanceSet,
+    tracks_distances: bool,
+    /// Depth up to which `marks` reflect the state
+    synced: usize,
+    length: usize,
+}
+
+impl PruneScratch {
+
+    /// Start over with an empty ruler of length `length`, to be checked by `pruner`
+    pub(super) fn reset<P: Pruner + ?Sized>(&mut self, length: usize, pruner: &P) {
+        self.marks.clear();
+        self.marks.extend([0, length as GInt]);
+        self.distances.clear();
+        self.tracks_distances = pruner.needs_distances();
+        if self.tracks_distances {
+            self.distances.add_mark(0);
+            self.distances.add_mark(length as GInt);
+        }
+        self.synced = 0;
+        self.length = length;
+    }
+
+    /// Whether our partial ruler can be checked by `pruner` for rulers of length `length` without a reset
+    pub(super) fn is_ready<P: Pruner + ?Sized>(&self, length: usize, pruner: &P) -> bool {
+        self.length == length && self.tracks_distances == pruner.needs_distances()
+    }
+
+    /// Forget our partial ruler, for when the state was moved somewhere else
+    pub(super) fn invalidate(&mut self) {
+        self.length = 0;
+    }
+
+    /// Forget every mark placed after `depth`
+    pub(super) fn truncate(&mut self, depth: usize) {
+        // The marks 0 and `length` stay
+        while let [_, .., m, _] = self.marks[..] {
+            if m <= depth as GInt {
+                break
+            }
+            self.marks.remove(self.marks.len() - 2);
+            if self.tracks_distances {
+                self.distances.remove_mark(m);
+            }
+        }
+        self.synced = usize::min(self.synced, depth);
+    }
+
+    /// Place a new mark, larger than every other mark
+    pub(super) fn push(&mut self, m: GInt) {
+        self.marks.insert(self.marks.len() - 1, m);
+        if self.tracks_distances {
+            self.distances.add_mark(m);
+        }
+    }
+
+    pub(super) fn synced(&self) -> usize {
+        self.synced
+    }
+
+    pub(super) fn set_synced(&mut self, depth: usize) {
+        self.synced = depth;
+    }
+
+    /// Ask `pruner` about the node at `depth`
+    pub(super) fn check<P: Pruner + ?Sized>(&self, pruner: &P, depth: usize) -> Verdict {
+        pruner.check(&self.partial(&self.marks[..self.marks.len() - 1], depth, false))
+    }
+
+    /// Ask `pruner` about the leaf we are currently at
+    pub(super) fn check_leaf<P: Pruner + ?Sized>(&self, pruner: &P) -> Verdict {
+        pruner.check(&self.partial(&self.marks, self.length - 1, true))
+    }
+
+    fn partial<'a>(&'a self, marks: &'a [GInt], depth: usize, is_leaf: bool) -> PartialRuler<'a> {
+        PartialRuler {
+            marks,
+            distances: if self.tracks_distances { self.distances.counts() } else { &[] },
+            collisions: if self.tracks_distances { self.distances.collision_count() } else { 0 },
+            depth,
+            length: self.length,
+            is_leaf,
+        }
+    }
+}
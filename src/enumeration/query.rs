@@ -0,0 +1,461 @@
+//! A single builder to describe and run any enumeration of rulers.
+//!
+//! Every `enumerate_*` function of this module is a thin wrapper around a [`RulerQuery`]:
+//!
+//! ```
+//! # use ogr_rust::enumeration::{RulerQuery, Pruning};
+//! let rulers = RulerQuery::new()
+//!     .order(5)
+//!     .lengths(2, 20)
+//!     .golomb(true)
+//!     .pruning(Pruning::Backtracking)
+//!     .collect();
+//! ```
+
+// `#[new]` constructors expand to non-local impls with pyo3 0.20
+#![cfg_attr(feature = "python", allow(non_local_definitions))]
+
+use std::iter::once;
+use std::sync::Arc;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+use crate::{GolombRuler, Ruler};
+use crate::error::{OgrError, Result};
+use super::constraints::RulerConstraints;
+use super::iterators::*;
+use super::pruners::*;
+use super::search_tree::{SearchTree, TreeRecorder, record_lengths};
+
+/// How a [`RulerQuery`] prunes the tree of ruler states
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pruning {
+    /// Visit every ruler, then filter by order
+    None,
+    /// Prune subtrees with too many marks
+    MarkCount,
+    /// Prune by mark count and [`FirstOrderDistances`], then prune subtrees as soon as two pairs of
+    /// marks at most `depth` marks apart have the same distance, see [`DistanceOrders`]
+    Depth(usize),
+    /// Prune every subtree as soon as it breaks the golomb property. Implies `golomb(true)`
+    Backtracking,
+}
+
+impl Pruning {
+    /// Parse a pruning strategy from its python name
+    #[cfg(feature = "python")]
+    fn parse(strategy: &str, depth: usize) -> Result<Pruning> {
+        match strategy {
+            "none" => Ok(Pruning::None),
+            "mark_count" => Ok(Pruning::MarkCount),
+            "depth" => Ok(Pruning::Depth(depth)),
+            "backtracking" => Ok(Pruning::Backtracking),
+            _ => Err(OgrError::InvalidArgument {
+                name: "pruning strategy".to_string(),
+                reason: format!("unknown strategy '{}', expected one of 'none', 'mark_count', 'depth' or 'backtracking'", strategy),
+            })
+        }
+    }
+
+    /// The pruner implementing this strategy for rulers with `order` marks
+    fn pruner(self, order: usize, constraints: &RulerConstraints) -> Box<dyn Pruner> {
+        match self {
+            Pruning::None => Box::new(NoPruning),
+            Pruning::MarkCount => Box::new(MarkCount(order)),
+            Pruning::Depth(depth) => Box::new(MarkCount(order).and(FirstOrderDistances).and(DistanceOrders(depth))),
+            Pruning::Backtracking => Box::new(MarkCount(order).and(GolombProperty).and(constraints.clone())),
+        }
+    }
+}
+
+/// What [`RulerQuery::run`] returns
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputMode {
+    List,
+    Iter,
+    Count,
+}
+
+/// The result of [`RulerQuery::run`]
+pub enum QueryOutput {
+    List(Vec<Ruler>),
+    Iter(Box<dyn Iterator<Item = Ruler> + Send>),
+    Count(usize),
+}
+
+/// Description of an enumeration of rulers.
+///
+/// * `order` - Order, or inclusive range of orders, of the rulers. Defaults to every order
+/// * `length` - Length, or inclusive range of lengths, of the rulers. Defaults to every length
+/// * `golomb` - Only keep golomb rulers
+/// * `pruning` - How the tree of ruler states is pruned, see [`Pruning`]
+/// * `symmetry` - Only keep one ruler out of every pair of mirror images
+/// * `constraints` - Constraints every ruler must satisfy. They prune the tree when backtracking and filter rulers otherwise
+/// * `pruner` - A custom [`Pruner`], combined with the pruning strategy so that both must accept a ruler
+/// * `output` - Whether [`RulerQuery::run`] returns a list, an iterator or a count
+///
+/// Rulers are produced by increasing length. Within a length, strategies that prune by order
+/// produce every ruler of the smallest order first.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "python", pyclass)]
+pub struct RulerQuery {
+    min_order: usize,
+    max_order: usize,
+    min_length: usize,
+    max_length: Option<usize>,
+    golomb: bool,
+    pruning: Pruning,
+    symmetry: bool,
+    constraints: Option<RulerConstraints>,
+    pruner: Option<Arc<dyn Pruner>>,
+    /// Set when `pruner` calls a python function, to report its exceptions
+    #[cfg(feature = "python")]
+    py_pruner: Option<Arc<PyPruner>>,
+    output: OutputMode,
+}
+
+impl Default for RulerQuery {
+    fn default() -> Self {
+        RulerQuery {
+            min_order: 1,
+            max_order: usize::MAX,
+            min_length: 0,
+            max_length: None,
+            golomb: false,
+            pruning: Pruning::None,
+            symmetry: false,
+            constraints: None,
+            pruner: None,
+            #[cfg(feature = "python")]
+            py_pruner: None,
+            output: OutputMode::List,
+        }
+    }
+}
+
+impl RulerQuery {
+    pub fn new() -> Self {
+        RulerQuery::default()
+    }
+}
+
+#[cfg_attr(feature = "python", pymethods)]
+impl RulerQuery {
+
+    #[cfg(feature = "python")]
+    #[new]
+    fn py_new() -> Self {
+        RulerQuery::default()
+    }
+
+    #[cfg(feature = "python")]
+    fn __repr__(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    /// Only keep rulers with exactly `order` marks
+    pub fn order(&self, order: usize) -> Self {
+        self.orders(order, order)
+    }
+
+    /// Only keep rulers with between `min_order` and `max_order` marks, inclusive
+    pub fn orders(&self, min_order: usize, max_order: usize) -> Self {
+        RulerQuery { min_order, max_order, ..self.clone() }
+    }
+
+    /// Only keep rulers of length `length`
+    pub fn length(&self, length: usize) -> Self {
+        self.lengths(length, length)
+    }
+
+    /// Only keep rulers with a length between `min_length` and `max_length`, inclusive
+    pub fn lengths(&self, min_length: usize, max_length: usize) -> Self {
+        RulerQuery { min_length, max_length: Some(max_length), ..self.clone() }
+    }
+
+    /// Only keep rulers with the golomb property
+    pub fn golomb(&self, golomb: bool) -> Self {
+        RulerQuery { golomb, ..self.clone() }
+    }
+
+    /// Only keep the smallest of every ruler and its mirror image
+    pub fn symmetry(&self, symmetry: bool) -> Self {
+        RulerQuery { symmetry, ..self.clone() }
+    }
+
+    /// Only keep rulers satisfying `constraints`
+    pub fn constraints(&self, constraints: RulerConstraints) -> Self {
+        RulerQuery { constraints: Some(constraints), ..self.clone() }
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl RulerQuery {
+
+    /// Set the pruning strategy: one of `"none"`, `"mark_count"`, `"depth"` or `"backtracking"`
+    #[pyo3(name = "pruning", signature = (strategy, depth=1))]
+    fn py_pruning(&self, strategy: &str, depth: usize) -> PyResult<Self> {
+        Ok(self.pruning(Pruning::parse(strategy, depth)?))
+    }
+
+    /// Only keep rulers accepted by the python function `func(partial: PartialRuler)`.
+    ///
+    /// `func` returns `True`/`"keep"`, `"reject"` or `False`/`"prune"`, and is called on every node of the tree,
+    /// unless `leaves_only` is set. Python pruners are much slower than the built-in strategies, so keep using one to
+    /// make the tree small.
+    #[pyo3(name = "pruner", signature = (func, leaves_only=false))]
+    fn py_pruner(&self, func: PyObject, leaves_only: bool) -> Self {
+        let py_pruner = Arc::new(PyPruner::new(func, leaves_only));
+        RulerQuery {
+            pruner: Some(py_pruner.clone()),
+            py_pruner: Some(py_pruner),
+            ..self.clone()
+        }
+    }
+
+    /// Set what `run` returns: one of `"list"`, `"iter"` or `"count"`
+    #[pyo3(name = "output")]
+    fn py_output(&self, mode: &str) -> PyResult<Self> {
+        let mode = match mode {
+            "list" => OutputMode::List,
+            "iter" => OutputMode::Iter,
+            "count" => OutputMode::Count,
+            _ => return Err(OgrError::InvalidArgument {
+                name: "output mode".to_string(),
+                reason: format!("unknown mode '{}', expected one of 'list', 'iter' or 'count'", mode),
+            }.into())
+        };
+        Ok(self.output(mode))
+    }
+
+    /// Return every ruler matching this query, as `GolombRuler` objects when `golomb` is set
+    #[pyo3(name = "list")]
+    fn py_list(&self, py: Python) -> PyResult<PyObject> {
+        self.check_bounded()?;
+        let rulers = self.collect();
+        self.take_error()?;
+        Ok(match self.golomb {
+            true => rulers.into_iter().map(GolombRuler::new_unchecked).collect::<Vec<_>>().into_py(py),
+            false => rulers.into_py(py),
+        })
+    }
+
+    /// Return a lazy iterator over the rulers matching this query, as `GolombRuler` objects when `golomb` is set
+    #[pyo3(name = "iter")]
+    fn py_iter(&self) -> RulerQueryIterator {
+        RulerQueryIterator { rulers: self.iter(), golomb: self.golomb, py_pruner: self.py_pruner.clone() }
+    }
+
+    fn __iter__(&self) -> RulerQueryIterator {
+        self.py_iter()
+    }
+
+    /// Count the rulers matching this query
+    #[pyo3(name = "count")]
+    fn py_count(&self) -> PyResult<usize> {
+        self.check_bounded()?;
+        let count = self.count();
+        self.take_error()?;
+        Ok(count)
+    }
+
+    /// Run this query, returning a list, an iterator or a count depending on `output`
+    #[pyo3(name = "run")]
+    fn py_run(&self, py: Python) -> PyResult<PyObject> {
+        Ok(match self.output {
+            OutputMode::List => self.py_list(py)?,
+            OutputMode::Iter => self.py_iter().into_py(py),
+            OutputMode::Count => self.py_count()?.into_py(py),
+        })
+    }
+}
+
+impl RulerQuery {
+
+    /// Set the pruning strategy
+    pub fn pruning(&self, pruning: Pruning) -> Self {
+        RulerQuery { pruning, ..self.clone() }
+    }
+
+    /// Only keep rulers accepted by `pruner`, on top of the pruning strategy.
+    ///
+    /// Rulers `[0]` and `[0, 1]` have no tree to traverse and are never shown to `pruner`.
+    pub fn with_pruner(&self, pruner: impl Pruner + 'static) -> Self {
+        RulerQuery {
+            pruner: Some(Arc::new(pruner)),
+            #[cfg(feature = "python")]
+            py_pruner: None,
+            ..self.clone()
+        }
+    }
+
+    /// Set what [`RulerQuery::run`] returns
+    pub fn output(&self, output: OutputMode) -> Self {
+        RulerQuery { output, ..self.clone() }
+    }
+
+    /// Run this query, returning a list, an iterator or a count depending on `output`.
+    ///
+    /// Without a maximum length, lists and counts never finish.
+    pub fn run(&self) -> QueryOutput {
+        match self.output {
+            OutputMode::List => QueryOutput::List(self.collect()),
+            OutputMode::Iter => QueryOutput::Iter(self.iter()),
+            OutputMode::Count => QueryOutput::Count(self.count()),
+        }
+    }
+
+    /// Return every ruler matching this query
+    pub fn collect(&self) -> Vec<Ruler> {
+        self.iter().collect()
+    }
+
+    /// Return every golomb ruler matching this query, setting `golomb`
+    pub fn collect_golomb(&self) -> Vec<GolombRuler> {
+        self.iter_golomb().collect()
+    }
+
+    /// Count the rulers matching this query
+    pub fn count(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Return a lazy iterator over the golomb rulers matching this query, setting `golomb`
+    pub fn iter_golomb(&self) -> impl Iterator<Item = GolombRuler> + Send {
+        self.golomb(true).iter().map(GolombRuler::new_unchecked)
+    }
+
+    /// Return a lazy iterator over the rulers matching this query
+    pub fn iter(&self) -> Box<dyn Iterator<Item = Ruler> + Send> {
+
+        let query = self.clone();
+        let lengths: Box<dyn Iterator<Item = usize> + Send> = match self.max_length {
+            Some(max_length) => Box::new(self.min_length..=max_length),
+            None => Box::new(self.min_length..),
+        };
+
+        Box::new(lengths.flat_map(move |length| query.iter_length(length)))
+    }
+
+    /// Return a lazy iterator over the rulers of length `length` matching this query
+    fn iter_length(&self, length: usize) -> Box<dyn Iterator<Item = Ruler> + Send> {
+
+        let query = self.clone();
+        let keep = move |r: &Ruler| {
+            (query.min_order..=query.max_order).contains(&r.order())
+                && (!query.golomb || r.is_golomb_ruler())
+                && (!query.symmetry || *r == r.canonical())
+                && query.constraints.as_ref().is_none_or(|c| c.admits(r))
+        };
+
+        // Rulers [0] and [0, 1] have no state to traverse
+        if length < 2 {
+            return Box::new(once(Ruler::from_id(length)).filter(keep))
+        }
+
+        // Orders of the rulers of this length that we are interested in
+        let orders = usize::max(self.min_order, 2)..=usize::min(self.max_order, length + 1);
+        let query = self.clone();
+
+        match self.pruning {
+            // Every order is visited at once
+            Pruning::None => Box::new(PrunerIterator::new_unchecked(length, self.order_pruner(0)).filter(keep)),
+            _ => Box::new(orders.flat_map(move |order| PrunerIterator::new_unchecked(length, query.order_pruner(order))).filter(keep)),
+        }
+    }
+
+    /// The pruner of our strategy for rulers with `order` marks, combined with our custom pruner
+    fn order_pruner(&self, order: usize) -> Box<dyn Pruner> {
+        let constraints = self.constraints.clone().unwrap_or_default();
+        let pruner = self.pruning.pruner(order, &constraints);
+        match &self.pruner {
+            Some(custom) => Box::new(pruner.and(custom.clone())),
+            None => pruner,
+        }
+    }
+
+    /// Record the search trees traversed by this query, for lengths up to its maximum length of at most [`MAX_TREE_LENGTH`](super::MAX_TREE_LENGTH).
+    ///
+    /// Every order is traversed at once, keeping the nodes that any of them keeps. Nodes are only attributed to the order
+    /// when the query has a single order. Leaves are yielded when the pruning strategy accepts them, and the golomb
+    /// property when `golomb` is set: the other filters of the query only see complete rulers and aren't shown.
+    pub fn search_tree(&self) -> Result<SearchTree> {
+
+        self.check_bounded()?;
+        let max_length = self.max_length.unwrap_or_default();
+        let lengths = usize::max(self.min_length, 2)..=max_length;
+
+        let pruner = match self.pruning {
+            Pruning::None => self.order_pruner(0),
+            _ => {
+                let mut orders = usize::max(self.min_order, 2)..=usize::min(self.max_order, max_length + 1);
+                match orders.next() {
+                    Some(first) => orders.fold(self.order_pruner(first), |pruner, order| Box::new(pruner.or(self.order_pruner(order)))),
+                    None => return Ok(SearchTree::default()),
+                }
+            }
+        };
+
+        let order = (self.min_order == self.max_order).then_some(self.min_order);
+        record_lengths(TreeRecorder::new(pruner, order, self.golomb), lengths)
+    }
+
+    /// Lists, counts and search trees need a maximum length to ever finish
+    fn check_bounded(&self) -> Result<()> {
+        match self.max_length {
+            Some(_) => Ok(()),
+            None => Err(OgrError::InvalidArgument {
+                name: "max_length".to_string(),
+                reason: "a maximum length is required to list, count or record rulers".to_string(),
+            })
+        }
+    }
+
+    /// Record our search trees, reporting the exceptions of a python pruner
+    #[cfg(feature = "python")]
+    pub(super) fn py_search_tree(&self) -> PyResult<SearchTree> {
+        let tree = self.search_tree()?;
+        self.take_error()?;
+        Ok(tree)
+    }
+
+    /// Report the first exception raised by a python pruner
+    #[cfg(feature = "python")]
+    fn take_error(&self) -> PyResult<()> {
+        match &self.py_pruner {
+            Some(py_pruner) => py_pruner.take_error(),
+            None => Ok(())
+        }
+    }
+}
+
+/// Python iterator over the rulers of a [`RulerQuery`]
+#[cfg(feature = "python")]
+#[pyclass]
+pub struct RulerQueryIterator {
+    rulers: Box<dyn Iterator<Item = Ruler> + Send>,
+    /// Yield `GolombRuler` objects, the query only keeping golomb rulers
+    golomb: bool,
+    py_pruner: Option<Arc<PyPruner>>,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl RulerQueryIterator {
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<'_, Self>, py: Python) -> PyResult<Option<PyObject>> {
+        let ruler = slf.rulers.next();
+        if let Some(py_pruner) = &slf.py_pruner {
+            py_pruner.take_error()?;
+        }
+        Ok(ruler.map(|r| match slf.golomb {
+            true => GolombRuler::new_unchecked(r).into_py(py),
+            false => r.into_py(py),
+        }))
+    }
+}
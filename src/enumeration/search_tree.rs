@@ -0,0 +1,282 @@
+//! Record the search tree visited by an enumeration, and export it as Graphviz DOT or JSON.
+//!
+//! Every node of the tree is the prefix of a [`RulerState`]: its first `depth` values decide whether the positions
+//! `1..=depth` are marks. Going left appends a `0`, going right appends a `1`, and pruning a node skips its whole
+//! subtree. Drawing the nodes that an iterator actually visits shows how `go_left_mut` and `skip_subtree_mut`
+//! move through the tree.
+//!
+//! Trees grow as `2^length`, so only lengths up to [`MAX_TREE_LENGTH`] can be recorded.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::ops::RangeInclusive;
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "python")]
+use pyo3::exceptions;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+use crate::GInt;
+use crate::error::{OgrError, Result};
+use super::iterators::PrunerIterator;
+#[cfg(feature = "python")]
+use super::pruners::PyPruner;
+use super::pruners::{Pruner, Verdict, PartialRuler, MarkCount};
+#[cfg(feature = "python")]
+use super::query::RulerQuery;
+use super::state::RulerState;
+
+/// What happened at a node of the search tree
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeOutcome {
+    /// The node was kept and its children were visited
+    Explored,
+    /// The node is a leaf that the iterator yielded
+    Yielded,
+    /// The node can't lead to a ruler with the requested order
+    PrunedByOrder,
+    /// The node repeats a distance
+    PrunedByGolomb,
+    /// The node was pruned for any other reason
+    Pruned,
+}
+
+impl NodeOutcome {
+
+    fn name(self) -> &'static str {
+        match self {
+            NodeOutcome::Explored => "explored",
+            NodeOutcome::Yielded => "yielded",
+            NodeOutcome::PrunedByOrder => "pruned_by_order",
+            NodeOutcome::PrunedByGolomb => "pruned_by_golomb",
+            NodeOutcome::Pruned => "pruned",
+        }
+    }
+
+    fn color(self) -> &'static str {
+        match self {
+            NodeOutcome::Explored => "white",
+            NodeOutcome::Yielded => "palegreen",
+            NodeOutcome::PrunedByOrder => "orange",
+            NodeOutcome::PrunedByGolomb => "tomato",
+            NodeOutcome::Pruned => "lightgrey",
+        }
+    }
+}
+
+/// A visited node of the search tree
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreeNode {
+    /// Length of the rulers of the tree
+    pub length: usize,
+    /// The prefix of the state at this node, empty at the root
+    pub state: Vec<bool>,
+    pub outcome: NodeOutcome,
+}
+
+impl TreeNode {
+
+    /// Unique identifier of this node among the trees of every length
+    fn id(&self) -> String {
+        format!("L{}_{}", self.length, self.state.to_string())
+    }
+
+    fn label(&self) -> String {
+        match self.state.len() {
+            0 => format!("length {}", self.length),
+            _ => self.state.to_string(),
+        }
+    }
+
+    fn parent(&self) -> Option<TreeNode> {
+        let (_, prefix) = self.state.split_last()?;
+        Some(TreeNode { length: self.length, state: prefix.to_vec(), outcome: NodeOutcome::Explored })
+    }
+}
+
+/// The nodes visited by an enumeration, in the order they were first visited
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchTree {
+    pub nodes: Vec<TreeNode>,
+}
+
+impl SearchTree {
+
+    /// Graphviz DOT representation, with nodes labelled by their state and filled by outcome
+    pub fn to_dot(&self) -> String {
+
+        let mut dot = String::from("digraph search_tree {\n    node [shape=box, style=filled, fontname=monospace];\n");
+
+        for node in &self.nodes {
+            writeln!(dot, "    {} [label=\"{}\", fillcolor={}];", node.id(), node.label(), node.outcome.color()).unwrap();
+        }
+        for node in &self.nodes {
+            if let Some(parent) = node.parent() {
+                let side = if node.state[node.state.len() - 1] { "1" } else { "0" };
+                writeln!(dot, "    {} -> {} [label=\"{}\"];", parent.id(), node.id(), side).unwrap();
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// JSON array of nodes `{"id", "parent", "length", "state", "outcome"}`, where the root's parent is null
+    pub fn to_json(&self) -> String {
+
+        let nodes: Vec<String> = self.nodes.iter().map(|node| {
+            let parent = node.parent().map_or(String::from("null"), |p| format!("\"{}\"", p.id()));
+            format!(
+                "{{\"id\": \"{}\", \"parent\": {}, \"length\": {}, \"state\": \"{}\", \"outcome\": \"{}\"}}",
+                node.id(), parent, node.length, node.state.to_string(), node.outcome.name()
+            )
+        }).collect();
+
+        format!("[{}]", nodes.join(", "))
+    }
+}
+
+/// Visited nodes, and the index of every node by length and state
+#[derive(Debug, Default)]
+struct Recorded {
+    nodes: Vec<TreeNode>,
+    index: HashMap<(usize, Vec<bool>), usize>,
+}
+
+/// A [`Pruner`] recording every node it is asked about, to plug into any iterator driven by a pruner.
+///
+/// Pruned nodes are attributed to the order when [`MarkCount`] with `order` prunes them, to the golomb check when they
+/// repeat a distance, and to other reasons otherwise. With `golomb` set, leaves that repeat a distance are rejected, as
+/// `RulerQuery::golomb(true)` filters them.
+#[derive(Debug)]
+pub struct TreeRecorder<P> {
+    pruner: P,
+    order: Option<usize>,
+    golomb: bool,
+    recorded: Mutex<Recorded>,
+}
+
+impl<P: Pruner> TreeRecorder<P> {
+
+    pub fn new(pruner: P, order: Option<usize>, golomb: bool) -> Self {
+        TreeRecorder { pruner, order, golomb, recorded: Mutex::default() }
+    }
+
+    /// The nodes recorded so far
+    pub fn tree(&self) -> SearchTree {
+        SearchTree { nodes: self.recorded.lock().unwrap().nodes.clone() }
+    }
+
+    /// Record the outcome of `partial`. Nodes that are checked again, like leaves, keep their latest outcome.
+    fn record(&self, partial: &PartialRuler, outcome: NodeOutcome) {
+
+        let state: Vec<bool> = (1..=partial.depth as GInt).map(|p| partial.marks.binary_search(&p).is_ok()).collect();
+        let node = TreeNode { length: partial.length, state, outcome };
+
+        let Recorded { nodes, index } = &mut *self.recorded.lock().unwrap();
+        match index.get(&(node.length, node.state.clone())) {
+            Some(idx) => nodes[*idx].outcome = outcome,
+            None => {
+                index.insert((node.length, node.state.clone()), nodes.len());
+                nodes.push(node);
+            }
+        }
+    }
+}
+
+impl<P: Pruner> Pruner for TreeRecorder<P> {
+    fn check(&self, partial: &PartialRuler) -> Verdict {
+
+        let mut verdict = self.pruner.check(partial);
+        if self.golomb && partial.is_leaf && partial.collisions > 0 {
+            verdict = Verdict::RejectLeaf;
+        }
+
+        let outcome = match verdict {
+            Verdict::Keep if partial.is_leaf => NodeOutcome::Yielded,
+            Verdict::Keep => NodeOutcome::Explored,
+            _ if self.order.is_some_and(|order| MarkCount(order).check(partial) != Verdict::Keep) => NodeOutcome::PrunedByOrder,
+            _ if partial.collisions > 0 => NodeOutcome::PrunedByGolomb,
+            _ => NodeOutcome::Pruned,
+        };
+
+        self.record(partial, outcome);
+        verdict
+    }
+}
+
+/// Longest rulers whose search tree can be recorded, as trees grow as `2^length`
+pub const MAX_TREE_LENGTH: usize = 20;
+
+/// Record the search trees that `pruner` visits for the rulers of every length from 2 up to `max_length`.
+///
+/// `max_length` can be at most [`MAX_TREE_LENGTH`]. Use [`RulerQuery::search_tree`](super::RulerQuery::search_tree) to record the trees of a query.
+pub fn record_search_tree<P: Pruner>(pruner: P, max_length: usize) -> Result<SearchTree> {
+    record_lengths(TreeRecorder::new(pruner, None, false), 2..=max_length)
+}
+
+/// Record the search trees of every length of `lengths` with `recorder`
+pub(super) fn record_lengths<P: Pruner>(recorder: TreeRecorder<P>, lengths: RangeInclusive<usize>) -> Result<SearchTree> {
+
+    if *lengths.end() > MAX_TREE_LENGTH {
+        return Err(OgrError::InvalidLength {
+            length: *lengths.end(),
+            reason: format!("search trees can only be recorded up to length {}", MAX_TREE_LENGTH),
+        })
+    }
+
+    let recorder = Arc::new(recorder);
+    for length in lengths {
+        PrunerIterator::new_unchecked(length, recorder.clone()).for_each(drop);
+    }
+
+    Ok(recorder.tree())
+}
+
+/// Export the search trees visited by `source` as Graphviz DOT or JSON.
+///
+/// `source` is either a `RulerQuery`, whose lengths are used, or a pruner function `func(partial: PartialRuler)`
+/// as accepted by `RulerQuery.pruner`, which needs a `max_length`. Lengths are at most 20.
+/// `format` is either `"dot"` or `"json"`.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(signature = (source, max_length=None, format="dot"))]
+pub fn export_search_tree(source: &PyAny, max_length: Option<usize>, format: &str) -> PyResult<String> {
+
+    let render = match format {
+        "dot" => SearchTree::to_dot,
+        "json" => SearchTree::to_json,
+        _ => return Err(OgrError::InvalidArgument {
+            name: "format".to_string(),
+            reason: format!("unknown format '{}', expected 'dot' or 'json'", format),
+        }.into()),
+    };
+
+    if let Ok(query) = source.extract::<RulerQuery>() {
+        if max_length.is_some() {
+            return Err(OgrError::InvalidArgument {
+                name: "max_length".to_string(),
+                reason: "a query records its own lengths, set them with RulerQuery.lengths".to_string(),
+            }.into())
+        }
+        return Ok(render(&query.py_search_tree()?))
+    }
+
+    if !source.is_callable() {
+        return Err(exceptions::PyTypeError::new_err(format!("Expected a RulerQuery or a pruner function, got {}", source)))
+    }
+
+    let Some(max_length) = max_length else {
+        return Err(OgrError::InvalidArgument {
+            name: "max_length".to_string(),
+            reason: "a maximum length is required to record the search tree of a pruner".to_string(),
+        }.into())
+    };
+
+    let pruner = Arc::new(PyPruner::new(source.into(), false));
+    let tree = record_search_tree(pruner.clone(), max_length)?;
+    pruner.take_error()?;
+
+    Ok(render(&tree))
+}
@@ -16,140 +16,66 @@
 //! - 0 is trivially always a mark on our ruler
 //! - The `length` mark can always be recovered by inspecting the `.len()` of our `state`.
 
-use crate::{Ruler, GolombRuler, GInt, dist};
+use crate::{Ruler, GInt};
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 
-const TWO: u64 = 2;
+use super::pruners::{Pruner, Verdict, MarkCount, PruneScratch};
 
 /// Trait to add functionality to a vector of booleans
+///
+/// Traversal happens in place: every step mutates the state rather than returning a new vector,
+/// so that a single buffer can be reused for an entire enumeration. Steps return `false` when
+/// there is no next state, in which case the state is left somewhere traversal can't continue from either.
+///
+/// Which subtrees are explored is decided by a [`Pruner`], see [`RulerState::advance_with`].
 pub(super) trait RulerState {
-    fn jump_back(&self) -> Vec<bool>;
-    fn to_u64(&self) -> Option<u64>;
-    /// Count the number of true values in this iterator state
-    fn count_marks(&self) -> usize;
-    /// Return the next state with max number of marks `order` and max length `length`
-    fn next(&self, length: usize) -> Option<Vec<bool>>;
-    /// Test if every bool is true in this RulerState
-    fn all(&self) -> bool;
-    /// Append a false to the end of this vector
-    fn go_left(&self) -> Vec<bool>;
-    /// Bounce back one level of ancestry, then go right
-    fn back_one_then_right(&self) -> Vec<bool>;
-    /// Traverse upwards until we've reached an untouched node
-    fn backtrack(&self) -> Vec<bool>;
     fn to_string(&self) -> String;
-    /// Convert the current state into a golomb ruler
-    fn to_ruler(&self) -> GolombRuler;
-    fn total_marks(&self) -> usize;
+    /// Convert the current state into a ruler
+    fn to_ruler(&self) -> Ruler;
+    /// Return the next state with exactly `order` marks and length `length`
     fn next_pruned(&self, order: usize, length: usize) -> Option<Vec<bool>>;
-    fn next_golomb_depth_1(&self, order: usize, length: usize) -> Option<Vec<bool>>;
-    fn contains(&self, value: GInt) -> bool;
-    fn is_golomb_ruler_order_1(&self) -> bool;
-    fn add_mark(&self) -> Option<Vec<bool>>;
-    fn pruned_propose_next(&self, order: usize) -> Option<Vec<bool>>;
-}
-
-impl RulerState for Vec<bool> {
-
-    fn to_u64(&self) -> Option<u64> {
+    /// Return the state that comes right before this one when iterating over every ruler of the same length
+    fn previous(&self) -> Option<Vec<bool>>;
 
-        let n = self.len();
-        if n > 64 {
-            None
-        } else {
-            let mut int: u64 = 0;
-            for i in 0..n {
-                if self[n - 1 - i] {
-                    int += TWO.pow((i).try_into().unwrap())
-                }
-            }
-            Some(int)
-        }
-    }
-
-    /// Used in enumeration algorithms. Check if the first order of distances are unique.
+    /* ---------------------------- In-place traversal --------------------------- */
+    /// Append a false to the end of this vector
+    fn go_left_mut(&mut self);
+    /// Skip over the subtree rooted at the first `depth` values of this state.
     ///
-    /// Consider the following array
+    /// With a depth of 2, send 0110 -> 1000 (instead of 0111).
+    /// Skipping at a depth of `self.len()` simply moves on to the next leaf.
     ///
-    /// `[1, 3, 4]`
+    /// Returns the index of the first value that changed, or None if there is no subtree left to the right.
+    fn skip_subtree_mut(&mut self, depth: usize) -> Option<usize>;
+    /// Inverse of `backtrack_mut`: 1000 -> 0111
     ///
-    /// 0th order: `[1, 3, 4]`
-    /// 1st order: `[2, 3]`
-    /// 2nd order: `[1]`
+    /// Returns false if this is the first state, full of zeros.
+    fn previous_mut(&mut self) -> bool;
+    /// Move to the state of the ruler whose id follows our own.
     ///
-    /// The idea is to prune our enumeration tree based on a O(2n) or O(n) check; n the number of marks on our ruler
-    /// We can later iterate over the returned rulers to actually filter them.
-    fn is_golomb_ruler_order_1(&self) -> bool {
-        // start with the first element of our ruler and subtract every other element
-        let ruler = self.to_ruler();
-        let base = ruler.marks[ruler.marks.len() - 1]; // 0 is implied, our first element
-
-        if ruler.marks.is_empty() || ruler.marks.len() == 1 {
-            return true
-        }
-
-        for m in &ruler.marks[0..(ruler.marks.len() - 1)] {
-            let d = dist(*m, base);
-            if self.contains(d) {
-                return false;
-            }
-        }
-        true
-    }
-
-    fn count_marks(&self) -> usize {
-        let mut count = 0;
-        self.iter().for_each(|b| if *b { count += 1 });
-        count
-    }
-
-    /// Return the total number of marks (including the implied start and end points).
-    fn total_marks(&self) -> usize {
-        self.count_marks() + 2
-    }
-
-    fn all(&self) -> bool {
-        for b in self {
-            if !b { return false }
-        }
-        true
-    }
-
-    fn go_left(&self) -> Vec<bool> {
-        let mut out = self.clone();
-        out.push(false);
-        out
-    }
-
-    fn back_one_then_right(&self) -> Vec<bool> {
-        let mut out = self.clone();
-        out[self.len() - 1] = true;
-        out
-    }
-
-    /// 0111 -> 1000
-    fn backtrack(&self) -> Vec<bool> {
-
-        let mut out = self.clone();
-        // Iterate backwards
-        for i in (0..self.len()).rev() {
-            if out[i] {
-                out[i] = false;
-            } else {
-                out[i] = true;
-                break;
-            }
-        }
+    /// Ids store mark 1 in their least significant bit, so they count in the opposite direction
+    /// of our traversal: 1100 -> 0010
+    ///
+    /// When every mark is used, we move on to the first ruler one unit longer: 111 -> 0000
+    fn next_id_mut(&mut self);
+    /// Move to the next ruler with length `length` accepted by `pruner`.
+    ///
+    /// Every time a position is decided, `pruner` is asked about the partial ruler built so far,
+    /// so that subtrees that can't contain an accepted ruler are skipped as soon as they are entered.
+    /// `scratch` keeps the marks and distances of the partial ruler between calls, and is only
+    /// updated for the positions that changed since the previous step.
+    fn advance_with<P: Pruner + ?Sized>(&mut self, length: usize, pruner: &P, scratch: &mut PruneScratch) -> bool;
+}
 
-        out
-    }
+impl RulerState for Vec<bool> {
 
     fn to_string(&self) -> String {
         self.iter().map(|b| if *b { '1' } else { '0' } ).collect()
     }
 
-    /// Convert the current RulerState into a full-fledged GolombRuler
-    fn to_ruler(&self) -> GolombRuler {
+    /// Convert the current RulerState into a full-fledged Ruler
+    fn to_ruler(&self) -> Ruler {
         // 0 is implied and the final element is implied as well!
         let length_of_ruler = self.len() + 1;
         let mut marks: Vec<GInt> = self.iter().enumerate().filter_map(|(idx, b)| {
@@ -161,248 +87,203 @@ impl RulerState for Vec<bool> {
         }).collect();
         marks.push(length_of_ruler as GInt);
 
-        GolombRuler {
+        Ruler {
             marks,
         }
     }
 
-    fn next(&self, length: usize) -> Option<Vec<bool>> {
-
-        if self.len() > length - 1 {
-            return None
-        }
-
-        if self.len() < length - 1 {
-
-            // Always 'go to the left'
-            let mut left = self.go_left();
-            while left.len() != length - 1 {
-                left = left.go_left();
-            }
-            Some(left)
-
-        } else {
-
-            // If our final element is 0, then we simply bounce back one and go to the right
-            if !self[self.len() - 1] {
-                Some(self.back_one_then_right())
-            } else {
-                // We need to back track!
-                // .. unless we should end iteration!
-                if self.all() {
-                    None
-                } else {
-                    Some(self.backtrack())
-                }
-            }
-        }
+    fn next_pruned(&self, order: usize, length: usize) -> Option<Vec<bool>> {
+        let mut out = self.clone();
+        out.advance_with(length, &MarkCount(order), &mut PruneScratch::default()).then_some(out)
     }
 
-    /// Attempt to add one mark to this state vector.
-    ///
-    /// If we can't increase the order tally, return None.
-    fn add_mark(&self) -> Option<Vec<bool>> {
-
+    fn previous(&self) -> Option<Vec<bool>> {
         let mut out = self.clone();
+        out.previous_mut().then_some(out)
+    }
 
-        // Iterating backwards, find the first zero
-        for i in (0..self.len()).rev() {
-            if !self[i] {
-                out[i] = true;
-                return Some(out);
-            }
-        }
-
-        // And return None if our state is full of ones
-        None
+    /* ---------------------------- In-place traversal --------------------------- */
+    fn go_left_mut(&mut self) {
+        self.push(false);
     }
 
-    /// Improved implementation of `next` by pruning trees that have too many points
-    fn next_pruned(&self, order: usize, length: usize) -> Option<Vec<bool>> {
+    fn skip_subtree_mut(&mut self, depth: usize) -> Option<usize> {
 
-        if self.len() > length - 1 {
-            return None
-        }
+        // If our prefix is full of ones, there is no subtree left to the right
+        let i = self[0..depth].iter().rposition(|b| !b)?;
+        self[i] = true;
+        self[i + 1..].iter_mut().for_each(|b| *b = false);
 
-        if self.len() < length - 1 {
-            return self.go_left().next_pruned(order, length);
-        }
+        Some(i)
+    }
 
-        let mut next = self.pruned_propose_next(order)?;
+    fn advance_with<P: Pruner + ?Sized>(&mut self, length: usize, pruner: &P, scratch: &mut PruneScratch) -> bool {
 
-        while next.total_marks() != order {
-            next = next.pruned_propose_next(order)?;
+        let n = length - 1;
+
+        if self.len() > n {
+            return false
         }
 
-        Some(next)
+        // Index from which our state differs from the one `scratch` was synced with
+        let mut from = if self.len() < n {
+            // Our left-most leaf is the first candidate
+            while self.len() != n {
+                self.go_left_mut();
+            }
+            scratch.reset(length, pruner);
+            0
+        } else {
+            if !scratch.is_ready(length, pruner) {
+                scratch.reset(length, pruner);
+            }
+            match self.skip_subtree_mut(n) {
+                Some(i) => i,
+                None => return false,
+            }
+        };
 
-    }
+        'search: loop {
 
-    fn next_golomb_depth_1(&self, order: usize, length: usize) -> Option<Vec<bool>> {
-        // First get next ruler
-        let mut next_pruned = self.next_pruned(order, length)?;
-        // if next_pruned.to_ruler().
+            scratch.truncate(from);
 
-        while !next_pruned.is_golomb_ruler_order_1() {
-            next_pruned = next_pruned.next_pruned(order, length)?;
-        }
+            // The root decides whether any ruler of this length is accepted
+            if scratch.synced() == 0 && scratch.check(pruner, 0) == Verdict::PruneSubtree {
+                self.iter_mut().for_each(|b| *b = true);
+                return false
+            }
 
-        Some(next_pruned)
-    }
+            for depth in scratch.synced() + 1..=n {
 
-    /// Skip over elements who are saturated
-    ///
-    /// With 2 total true values, send 010 -> 100 (instead of 011)
-    ///
-    /// This function should be called when we are about to add an additional mark
-    /// but we actually already have enough marks
-    /// The caller must protect against the case where the first boolean is 1.
-    fn jump_back(&self) -> Vec<bool> {
+                if self[depth - 1] {
+                    scratch.push(depth as GInt);
+                }
+                scratch.set_synced(depth);
+
+                if scratch.check(pruner, depth) == Verdict::PruneSubtree {
+                    match self.skip_subtree_mut(depth) {
+                        Some(i) => {
+                            from = i;
+                            continue 'search
+                        },
+                        None => return false,
+                    }
+                }
+            }
 
-        // Just roll back the first one that we encounter
-        let mut out = self.clone();
-        let mut j: usize = 0;
-        for i in (0..out.len()).rev() {
-            if out[i] {
-                out[i] = false;
-                j = i;
-                // out[i - 1] = true;
-                break
+            if scratch.check_leaf(pruner) == Verdict::Keep {
+                return true
             }
-        }
 
-        // Now go through consecutive ones
-        for i in (0..j).rev() {
-            // If our consecutive ones have ended,
-            if !out[i] {
-                out[i] = true;
-                break
-            } else {
-                out[i] = false;
+            match self.skip_subtree_mut(n) {
+                Some(i) => from = i,
+                None => return false,
             }
         }
-
-        out
     }
 
+    fn previous_mut(&mut self) -> bool {
 
-    /// Given a vector of booleans, propose the next pruned ruler.
-    ///
-    /// This function helps us implement an iterative version of `next_pruned` that doesn't use recursion
-    fn pruned_propose_next(&self, order: usize) -> Option<Vec<bool>> {
-
-        // If our final element is 0, then we simply bounce back one and go to the right
-        if !self[self.len() - 1] {
-
-            let n_marks = self.total_marks();
-
-            // If we have too many marks, we can't continue to the right.
-            if n_marks == order {
+        match self.iter().rposition(|b| *b) {
+            Some(i) => {
+                self[i] = false;
+                self[i + 1..].iter_mut().for_each(|b| *b = true);
+                true
+            },
+            None => false
+        }
+    }
 
-                // If our vector starts with 1, then we are totally done.
-                // This condition needs to change to: if the first (order - 2) elements are 1
-                if self.iter().take(order - 2).all(|b| *b) {
-                // if self[0] {
-                    None
-                } else {
-                    Some(self.jump_back())
-                }
-            } else {
-                self.add_mark()
-            }
+    fn next_id_mut(&mut self) {
 
-        } else {
-            // We need to back track!
-            // .. unless we should end iteration!
-            if self.all() {
-                None
-            } else {
-                Some(self.backtrack())
+        match self.iter().position(|b| !b) {
+            Some(i) => {
+                self[i] = true;
+                self[0..i].iter_mut().for_each(|b| *b = false);
+            },
+            None => {
+                self.iter_mut().for_each(|b| *b = false);
+                self.push(false);
             }
         }
     }
+}
 
-    /// Check if self contains the value `value`
-    fn contains(&self, value: GInt) -> bool {
+/// Return the state of the ruler with id `id`. Only valid for `id >= 1`, as the ruler `[0]` has no state.
+pub(super) fn state_from_id(id: usize) -> Vec<bool> {
 
-        let length = self.len() + 1;
+    // The most significant bit encodes the length of our ruler
+    let length = id.ilog2() as usize + 1;
 
-        if value < 0 {
-            return false
-        }
+    // Every other bit is a mark, with mark 1 stored in the least significant bit
+    (0..length - 1).map(|i| {
+        let mask = 1usize << i;
+        (id & mask) == mask
+    }).collect()
+}
 
-        if value == 0 || value == length as GInt {
-            true
-        } else if value as usize > length  {
-            false
-        } else {
-            self[(value - 1) as usize]
-        }
+/// Return the state that precedes the ruler with id `id` in the traversal of rulers with length `length`.
+///
+/// Iterators resuming from this state yield the ruler `id` (or the first valid ruler after it) next.
+/// When `id` refers to a shorter ruler, we return the starting pre-state, and when it refers to
+/// a longer ruler we return the final state so that iteration is over.
+pub(super) fn seek_state(id: usize, length: usize) -> Vec<bool> {
+
+    let pre_state = vec![false; length.saturating_sub(2)];
+    let id_length = if id == 0 { 0 } else { id.ilog2() as usize + 1 };
+
+    if id_length < length {
+        pre_state
+    } else if id_length > length {
+        vec![true; length.saturating_sub(1)]
+    } else {
+        state_from_id(id).previous().unwrap_or(pre_state)
     }
-
 }
 
-#[pymethods]
-impl GolombRuler {
+impl Ruler {
 
-    #[staticmethod]
-    pub fn from_id(id: usize) -> GolombRuler {
+    /// Create a new ruler from a given id.
+    pub fn from_id(id: usize) -> Ruler {
 
         if id == 0 {
-            GolombRuler {
+            Ruler {
                 marks: vec![],
             }
         } else if id == 1 {
-            GolombRuler {
+            Ruler {
                 marks: vec![1]
             }
         } else {
-
-            // Break the id into binary
-            // for bit in id.view_bits() {
-
-            // }
-            let length = id.ilog2() as usize + 1;
-
-            // Now that's the _length_ of our bit vector!
-            // println!("Magnitude: {}", magnitude);
-            let mut state = vec![false; length - 1];
-
-            for (i, b) in state.iter_mut().enumerate().take(length - 1) {
-
-                let mask = 1usize << i;
-                if (id & mask) == mask {
-                    *b = true
-                }
-            }
-
-            state.to_ruler()
+            state_from_id(id).to_ruler()
         }
     }
 
-    #[staticmethod]
-    pub fn from_ids(start_idx: usize, end_idx: usize) -> Vec<GolombRuler> {
-        (start_idx..end_idx).map(GolombRuler::from_id).collect::<Vec<GolombRuler>>()
-    }
-
-    /// Return the next _RULER_ with order `order` and length `length`, not necessarily the next golomb ruler
-    pub fn next_pruned(&self, order: usize, length: usize) -> Option<GolombRuler> {
-        Some(self.to_state().next_pruned(order, length)?.to_ruler())
+    pub fn from_ids(start_idx: usize, end_idx: usize) -> Vec<Ruler> {
+        (start_idx..end_idx).map(Ruler::from_id).collect::<Vec<Ruler>>()
     }
-
-
-
 }
 
-
-#[pymethods]
+#[cfg_attr(feature = "python", pymethods)]
 impl Ruler {
 
     /// Create a new ruler from a given id.
+    #[cfg(feature = "python")]
+    #[staticmethod]
+    #[pyo3(name = "from_id", text_signature = "(id: int)")]
+    fn py_from_id(id: usize) -> Ruler {
+        Ruler::from_id(id)
+    }
+
+    #[cfg(feature = "python")]
     #[staticmethod]
-    #[pyo3(text_signature = "(id: int)")]
-    fn from_id(id: usize) -> GolombRuler {
-        GolombRuler::from_id(id)
+    #[pyo3(name = "from_ids")]
+    fn py_from_ids(start_idx: usize, end_idx: usize) -> Vec<Ruler> {
+        Ruler::from_ids(start_idx, end_idx)
     }
 
-}
\ No newline at end of file
+    /// Return the next _RULER_ with order `order` and length `length`, not necessarily the next golomb ruler
+    pub fn next_pruned(&self, order: usize, length: usize) -> Option<Ruler> {
+        Some(self.to_state().next_pruned(order, length)?.to_ruler())
+    }
+}
@@ -0,0 +1,102 @@
+//! The error type shared by every fallible function of the crate, and the python exceptions it maps to.
+//!
+//! Every python exception derives from `ogr_rust.OgrError`, itself a `ValueError`:
+//!
+//! * `InvalidOrderError` - [`OgrError::InvalidOrder`]
+//! * `InvalidLengthError` - [`OgrError::InvalidLength`]
+//! * `RulerOverflowError` - [`OgrError::Overflow`]
+//! * `InvalidMarksError` - [`OgrError::InvalidMarks`] and [`OgrError::Extension`]
+//! * `RulerParseError` - [`OgrError::Parse`]
+//! * `OgrError` itself - [`OgrError::InvalidArgument`]
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use thiserror::Error;
+
+use crate::GInt;
+use crate::rulers::{ExtensionError, ParseRulerError};
+
+/// Why an operation on rulers failed
+#[derive(Error, Debug, Clone)]
+pub enum OgrError {
+    #[error("Invalid order {order}: {reason}")]
+    InvalidOrder { order: usize, reason: String },
+    #[error("Invalid length {length}: {reason}")]
+    InvalidLength { length: usize, reason: String },
+    #[error("{0} doesn't fit in a machine integer")]
+    Overflow(String),
+    #[error("Invalid marks {marks:?}: {reason}")]
+    InvalidMarks { marks: Vec<GInt>, reason: String },
+    #[error(transparent)]
+    Extension(Box<ExtensionError>),
+    #[error(transparent)]
+    Parse(#[from] ParseRulerError),
+    #[error("Invalid {name}: {reason}")]
+    InvalidArgument { name: String, reason: String },
+}
+
+pub type Result<T, E = OgrError> = std::result::Result<T, E>;
+
+// Boxed to keep every `Result` small, extension errors carry two distances
+impl From<ExtensionError> for OgrError {
+    fn from(err: ExtensionError) -> OgrError {
+        OgrError::Extension(Box::new(err))
+    }
+}
+
+/// Python exceptions raised for every variant of [`OgrError`]
+#[cfg(feature = "python")]
+pub mod exceptions {
+    use pyo3::create_exception;
+
+    create_exception!(ogr_rust, OgrError, pyo3::exceptions::PyValueError, "Base class of every error raised by ogr_rust");
+    create_exception!(ogr_rust, InvalidOrderError, OgrError, "The order of a ruler is out of range");
+    create_exception!(ogr_rust, InvalidLengthError, OgrError, "The length of a ruler is out of range");
+    create_exception!(ogr_rust, RulerOverflowError, OgrError, "A value doesn't fit in a machine integer");
+    create_exception!(ogr_rust, InvalidMarksError, OgrError, "The marks of a ruler are invalid");
+    create_exception!(ogr_rust, RulerParseError, OgrError, "Some text is not a ruler");
+}
+
+#[cfg(feature = "python")]
+impl From<OgrError> for PyErr {
+    fn from(err: OgrError) -> PyErr {
+        let msg = err.to_string();
+        match err {
+            OgrError::InvalidOrder { .. } => exceptions::InvalidOrderError::new_err(msg),
+            OgrError::InvalidLength { .. } => exceptions::InvalidLengthError::new_err(msg),
+            OgrError::Overflow(_) => exceptions::RulerOverflowError::new_err(msg),
+            OgrError::InvalidMarks { .. } | OgrError::Extension(_) => exceptions::InvalidMarksError::new_err(msg),
+            OgrError::Parse(_) => exceptions::RulerParseError::new_err(msg),
+            OgrError::InvalidArgument { .. } => exceptions::OgrError::new_err(msg),
+        }
+    }
+}
+
+/// Register our exceptions in the python module `m`
+#[cfg(feature = "python")]
+pub(crate) fn add_exceptions(py: Python, m: &PyModule) -> PyResult<()> {
+    use exceptions::*;
+    m.add("OgrError", py.get_type::<OgrError>())?;
+    m.add("InvalidOrderError", py.get_type::<InvalidOrderError>())?;
+    m.add("InvalidLengthError", py.get_type::<InvalidLengthError>())?;
+    m.add("RulerOverflowError", py.get_type::<RulerOverflowError>())?;
+    m.add("InvalidMarksError", py.get_type::<InvalidMarksError>())?;
+    m.add("RulerParseError", py.get_type::<RulerParseError>())?;
+    Ok(())
+}
+
+/// Rulers have at least one mark
+pub(crate) fn check_order(order: usize) -> Result<()> {
+    match order {
+        0 => Err(OgrError::InvalidOrder { order, reason: "rulers have at least one mark".to_string() }),
+        _ => Ok(()),
+    }
+}
+
+/// The tree of ruler states starts at length 2, as `[0]` and `[0, 1]` have no state
+pub(crate) fn check_tree_length(length: usize) -> Result<()> {
+    match length {
+        0 | 1 => Err(OgrError::InvalidLength { length, reason: "ruler states start at length 2".to_string() }),
+        _ => Ok(()),
+    }
+}
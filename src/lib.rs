@@ -27,22 +27,41 @@
 //! * `Golomb Property` - categorized by the fact that no two pairs of marks are the same distance apart.
 //! * `Golomb Ruler` - A `Ruler` satisfying the `Golomb Property`
 //!
+//! # Cargo features
+//!
+//! * `python` (default) - The `ogr_rust` python extension module, built with [PyO3]. Rust crates depending on
+//!   `ogr-rust` with `default-features = false` get the same rulers, iterators and algorithms without linking python.
+//!
+//! ```
+//! use ogr_rust::enumeration::enumerate_golomb_rulers;
+//!
+//! let rulers = enumerate_golomb_rulers(4, 6).unwrap();
+//! assert!(rulers.iter().all(|r| r.is_golomb_ruler() && r.length() <= 6));
+//! ```
+//!
 //! [Wikipedia]: https://en.wikipedia.org/wiki/Golomb_ruler
 //! [Polars]: https://pola.rs/
 //! [PyO3]: https://pyo3.rs/v0.20.3/
 //! [`ogr-py`]: https://pypi.org/project/ogr-py/
 
-
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 
-mod rulers;
+mod error;
+pub mod rulers;
 pub mod enumeration;
 
 use rulers::*;
+pub use error::OgrError;
+#[cfg(feature = "python")]
+pub use error::exceptions;
+pub use rulers::{GInt, generate_golomb_ruler_naive, generate_golomb_ruler_improved, Ruler, GolombRuler, RulerFormat, ParseRulerError, Collision, Distance, DistanceSet, SidonSequence, StepRule, ExtensionError, Canonical, Dedup, dedup_rulers, reconstruct_rulers, reconstruct_beltway, BeamSearch, BeamScore, lookahead_score};
 
 
+#[cfg(feature = "python")]
 #[pymodule]
-fn ogr_rust(_py: Python, m: &PyModule) -> PyResult<()> {
+fn ogr_rust(py: Python, m: &PyModule) -> PyResult<()> {
+    error::add_exceptions(py, m)?;
     m.add_function(wrap_pyfunction!(generate_golomb_ruler_naive, m)?)?;
     m.add_function(wrap_pyfunction!(generate_golomb_ruler_improved, m)?)?;
     m.add_function(wrap_pyfunction!(enumeration::enumerate_rulers, m)?)?;
@@ -50,7 +69,43 @@ fn ogr_rust(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(enumeration::enumerate_golomb_rulers, m)?)?;
     m.add_function(wrap_pyfunction!(enumeration::enumerate_pruned_rulers, m)?)?;
     m.add_function(wrap_pyfunction!(enumeration::enumerate_rulers_with_order, m)?)?;
+    m.add_function(wrap_pyfunction!(enumeration::enumerate_golomb_rulers_with_length, m)?)?;
     m.add_function(wrap_pyfunction!(enumeration::enumerate_golomb_rulers_pruned, m)?)?;
+    m.add_function(wrap_pyfunction!(enumeration::enumerate_golomb_rulers_pruned_with_length, m)?)?;
+    m.add_function(wrap_pyfunction!(enumeration::enumerate_golomb_rulers_depth, m)?)?;
+    m.add_function(wrap_pyfunction!(enumeration::enumerate_golomb_rulers_depth_with_length, m)?)?;
+    m.add_function(wrap_pyfunction!(enumeration::enumerate_golomb_rulers_constrained, m)?)?;
+    m.add_function(wrap_pyfunction!(enumeration::enumerate_golomb_rulers_constrained_with_length, m)?)?;
+    m.add_function(wrap_pyfunction!(enumeration::enumerate_golomb_rulers_with_prefix, m)?)?;
+    m.add_function(wrap_pyfunction!(enumeration::enumerate_ids, m)?)?;
+    m.add_function(wrap_pyfunction!(enumeration::enumerate_golomb_ids, m)?)?;
+    m.add_function(wrap_pyfunction!(enumeration::enumerate_ids_with_order, m)?)?;
+    m.add_function(wrap_pyfunction!(enumeration::enumerate_near_golomb_rulers, m)?)?;
+    m.add_function(wrap_pyfunction!(enumeration::enumerate_maximal_golomb_rulers, m)?)?;
+    m.add_function(wrap_pyfunction!(enumeration::count_maximal_golomb_rulers, m)?)?;
+    m.add_function(wrap_pyfunction!(enumeration::max_order_table, m)?)?;
+    m.add_function(wrap_pyfunction!(enumeration::max_order_rulers, m)?)?;
+    m.add_function(wrap_pyfunction!(enumeration::enumerate_golomb_rulers_meet_in_the_middle, m)?)?;
+    m.add_function(wrap_pyfunction!(enumeration::py_estimate_golomb_rulers_pruned, m)?)?;
+    m.add_function(wrap_pyfunction!(enumeration::export_search_tree, m)?)?;
+    m.add_function(wrap_pyfunction!(py_dedup_rulers, m)?)?;
+    m.add_function(wrap_pyfunction!(reconstruct_rulers, m)?)?;
+    m.add_function(wrap_pyfunction!(reconstruct_beltway, m)?)?;
+    m.add_function(wrap_pyfunction!(beam_search_ruler, m)?)?;
     m.add_class::<Ruler>()?;
+    m.add_class::<GolombRuler>()?;
+    m.add_class::<DistanceSet>()?;
+    m.add_class::<Distance>()?;
+    m.add_class::<Collision>()?;
+    m.add_class::<SidonSequence>()?;
+    m.add_class::<enumeration::RulerConstraints>()?;
+    m.add_class::<enumeration::RulerIdIterator>()?;
+    m.add_class::<enumeration::GolombRulerPrefixIterator>()?;
+    m.add_class::<enumeration::RulerQuery>()?;
+    m.add_class::<enumeration::RulerQueryIterator>()?;
+    m.add_class::<enumeration::PyPartialRuler>()?;
+    m.add_class::<RulerDedupIterator>()?;
+    m.add_class::<enumeration::MeetInTheMiddleStats>()?;
+    m.add_class::<enumeration::TreeEstimate>()?;
     Ok(())
 }
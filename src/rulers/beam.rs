@@ -0,0 +1,195 @@
+//! Beam search construction of short golomb rulers.
+//!
+//! Greedy constructions commit to every mark right away. A beam search instead keeps the `width` most
+//! promising partial rulers of every order, extends each of them with every valid mark, and keeps the best
+//! `width` extensions for the next order. Partial rulers are compared with a scoring function that looks
+//! ahead at how long they will end up.
+
+use std::fmt;
+use std::sync::Arc;
+#[cfg(feature = "python")]
+use std::sync::Mutex;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+use crate::GInt;
+use crate::error::{Result, check_order};
+use super::{DistanceSet, GolombRuler, Ruler};
+use super::rng::SplitMix64;
+
+/// Scores a partial ruler, given its distances and the order of the ruler we are building. Lower is better.
+pub type BeamScore = Arc<dyn Fn(&DistanceSet, usize) -> f64 + Send + Sync>;
+
+/// Default score of a partial ruler, looking ahead at the marks that are left to place.
+///
+/// The gaps between the remaining marks have to be distinct distances that aren't used yet, so the
+/// ruler is at least as long as its last mark plus the sum of the smallest free distances. Ties are broken
+/// in favour of rulers with more free distances below their length, which leave more room to place marks.
+pub fn lookahead_score(distances: &DistanceSet, order: usize) -> f64 {
+
+    let marks = distances.marks();
+    let last = marks[marks.len() - 1];
+    let remaining = order.saturating_sub(marks.len());
+
+    let projected: GInt = last + (1..).filter(|d| distances.count(*d) == 0).take(remaining).sum::<GInt>();
+    let free = (1..=last).filter(|d| distances.count(*d) == 0).count() as f64;
+
+    projected as f64 - free / (free + 1.0)
+}
+
+/// Configuration of a beam search. See the [module documentation](self).
+///
+/// * `width` - Number of partial rulers kept at every order
+/// * `horizon` - Largest gap between a new mark and the last mark of a partial ruler. Defaults to twice the order, or
+///   twice the mean gap of the partial ruler once that is larger. A partial ruler without any valid mark within its
+///   horizon is extended with the smallest valid mark past it, so the search always completes
+/// * `seed` - Ties between partial rulers with the same score are broken at random, reproducibly for a given seed
+/// * `score` - Scoring function of partial rulers, [`lookahead_score`] by default
+#[derive(Clone)]
+pub struct BeamSearch {
+    width: usize,
+    horizon: Option<GInt>,
+    seed: u64,
+    score: BeamScore,
+}
+
+impl fmt::Debug for BeamSearch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BeamSearch")
+            .field("width", &self.width)
+            .field("horizon", &self.horizon)
+            .field("seed", &self.seed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for BeamSearch {
+    fn default() -> Self {
+        BeamSearch {
+            width: 16,
+            horizon: None,
+            seed: 0,
+            score: Arc::new(lookahead_score),
+        }
+    }
+}
+
+impl BeamSearch {
+
+    pub fn new() -> Self {
+        BeamSearch::default()
+    }
+
+    /// Keep `width` partial rulers at every order
+    pub fn width(&self, width: usize) -> Self {
+        BeamSearch { width: usize::max(width, 1), ..self.clone() }
+    }
+
+    /// Only place new marks at most `horizon` past the last mark
+    pub fn horizon(&self, horizon: GInt) -> Self {
+        BeamSearch { horizon: Some(horizon), ..self.clone() }
+    }
+
+    /// Break ties with a random number generator seeded with `seed`
+    pub fn seed(&self, seed: u64) -> Self {
+        BeamSearch { seed, ..self.clone() }
+    }
+
+    /// Score partial rulers with `score`, lower being better
+    pub fn score(&self, score: impl Fn(&DistanceSet, usize) -> f64 + Send + Sync + 'static) -> Self {
+        BeamSearch { score: Arc::new(score), ..self.clone() }
+    }
+
+    /// Build a golomb ruler with `order` marks, returning the shortest one found.
+    ///
+    /// Fails for order 0.
+    pub fn run(&self, order: usize) -> Result<GolombRuler> {
+
+        check_order(order)?;
+        if order == 1 {
+            return Ok(GolombRuler::default())
+        }
+
+        let width = usize::max(self.width, 1);
+        let mut rng = SplitMix64::new(self.seed);
+        let mut beam = vec![DistanceSet::from_marks(&[0])];
+
+        for _ in 1..order {
+
+            let mut candidates: Vec<(f64, u64, DistanceSet)> = vec![];
+
+            for distances in &beam {
+
+                let marks = distances.marks();
+                let last = marks[marks.len() - 1];
+                let horizon = self.horizon.unwrap_or_else(|| GInt::max(2 * order as GInt, 2 * last / marks.len() as GInt));
+
+                let extend = |p: GInt| {
+                    let mut extended = distances.clone();
+                    extended.add_mark(p);
+                    Some(extended).filter(DistanceSet::is_golomb)
+                };
+
+                let mut extensions: Vec<DistanceSet> = (last + 1..=last + horizon).filter_map(extend).collect();
+                if extensions.is_empty() {
+                    // Marks past twice our length always keep the golomb property, so this always finds one
+                    extensions.extend((GInt::max(last + horizon, last) + 1..).find_map(extend));
+                }
+
+                for extended in extensions {
+                    candidates.push(((self.score)(&extended, order), rng.next_u64(), extended));
+                }
+            }
+
+            // A partial ruler takes a single place in the beam, however many ways it was reached
+            candidates.sort_by(|a, b| a.2.marks().cmp(b.2.marks()));
+            candidates.dedup_by(|a, b| a.2 == b.2);
+
+            candidates.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+            candidates.truncate(width);
+            beam = candidates.into_iter().map(|(_, _, distances)| distances).collect();
+        }
+
+        // Every member of the last beam is complete, and the shortest one wins
+        let best = beam.iter().min_by_key(|distances| distances.marks()[order - 1])
+            .expect("every partial ruler has an extension");
+        Ok(GolombRuler::new_unchecked(Ruler::from(best)))
+    }
+}
+
+/// Build a golomb ruler with `order` marks with a beam search keeping `width` partial rulers at every order.
+///
+/// `score(marks, order)` optionally replaces the default lookahead score, lower being better.
+/// Results only depend on `seed`, which breaks ties between partial rulers.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(signature = (order, width=16, horizon=None, seed=0, score=None))]
+pub fn beam_search_ruler(order: usize, width: usize, horizon: Option<GInt>, seed: u64, score: Option<PyObject>) -> PyResult<GolombRuler> {
+
+    let mut search = BeamSearch::new().width(width).seed(seed);
+    if let Some(horizon) = horizon {
+        search = search.horizon(horizon);
+    }
+
+    let error: Arc<Mutex<Option<PyErr>>> = Arc::new(Mutex::new(None));
+
+    if let Some(score) = score {
+        let error = error.clone();
+        search = search.score(move |distances, order| Python::with_gil(|py| {
+            let result = score.call1(py, (distances.marks().to_vec(), order)).and_then(|s| s.extract::<f64>(py));
+            result.unwrap_or_else(|err| {
+                error.lock().unwrap().get_or_insert(err);
+                f64::INFINITY
+            })
+        }));
+    }
+
+    let ruler = search.run(order);
+
+    let error = error.lock().unwrap().take();
+    match error {
+        Some(err) => Err(err),
+        None => Ok(ruler?),
+    }
+}
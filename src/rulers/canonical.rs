@@ -0,0 +1,139 @@
+//! Canonical forms of rulers, used to recognize duplicates when merging result sets.
+//!
+//! A ruler and its mirror image measure the same distances, so most searches only care about one of them.
+//! Their common canonical form is the lexicographically smallest of the two, see [`Ruler::canonical`].
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::types::PyIterator;
+
+use super::Ruler;
+#[cfg(feature = "python")]
+use super::GolombRuler;
+
+/// A ruler that compares and hashes by its canonical form, so that it is equal to its mirror image.
+#[derive(Clone, Debug)]
+pub struct Canonical(pub Ruler);
+
+impl PartialEq for Canonical {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.canonical() == other.0.canonical()
+    }
+}
+
+impl Eq for Canonical {}
+
+impl Hash for Canonical {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.canonical().hash(state)
+    }
+}
+
+impl PartialOrd for Canonical {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Canonical {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.canonical().cmp(&other.0.canonical())
+    }
+}
+
+/// The rulers we've already come across while deduplicating
+#[derive(Debug, Default)]
+struct SeenRulers {
+    seen: HashSet<Ruler>,
+    mirror: bool,
+}
+
+impl SeenRulers {
+
+    /// Remember `ruler`, returning false if it (or its mirror, when `mirror` is set) was already seen
+    fn insert(&mut self, ruler: &Ruler) -> bool {
+        let key = if self.mirror { ruler.canonical() } else { ruler.clone() };
+        self.seen.insert(key)
+    }
+}
+
+/// Iterator returned by [`dedup_rulers`]
+#[derive(Debug)]
+pub struct Dedup<I> {
+    rulers: I,
+    seen: SeenRulers,
+}
+
+impl<I> Iterator for Dedup<I> where I: Iterator, I::Item: AsRef<Ruler> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let seen = &mut self.seen;
+        self.rulers.find(|r| seen.insert(r.as_ref()))
+    }
+}
+
+/// Lazily drop every ruler that already appeared in `rulers`, keeping the first occurrence.
+///
+/// With `mirror`, a ruler is also dropped when its mirror image already appeared.
+/// This works on any iterator of [`Ruler`] or [`GolombRuler`].
+pub fn dedup_rulers<I>(rulers: I, mirror: bool) -> Dedup<I::IntoIter> where I: IntoIterator, I::Item: AsRef<Ruler> {
+    Dedup {
+        rulers: rulers.into_iter(),
+        seen: SeenRulers { seen: HashSet::new(), mirror },
+    }
+}
+
+/// Drop every ruler that already appeared in `rulers`, which can be a list or any iterable of `Ruler` or `GolombRuler`.
+///
+/// With `mirror`, a ruler is also dropped when its mirror image already appeared.
+/// Rulers are pulled from `rulers` lazily, so this works on never-ending streams.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "dedup_rulers", signature = (rulers, mirror=false))]
+pub(crate) fn py_dedup_rulers(rulers: &PyAny, mirror: bool) -> PyResult<RulerDedupIterator> {
+    Ok(RulerDedupIterator {
+        rulers: PyIterator::from_object(rulers)?.into(),
+        seen: SeenRulers { seen: HashSet::new(), mirror },
+    })
+}
+
+/// Python iterator over the rulers kept by `dedup_rulers`
+#[cfg(feature = "python")]
+#[pyclass]
+pub struct RulerDedupIterator {
+    rulers: Py<PyIterator>,
+    seen: SeenRulers,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl RulerDedupIterator {
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<'_, Self>, py: Python) -> PyResult<Option<PyObject>> {
+
+        let rulers = slf.rulers.clone_ref(py);
+
+        for item in rulers.as_ref(py) {
+            let item = item?;
+            let ruler: Ruler = match item.extract::<GolombRuler>() {
+                Ok(ruler) => ruler.into_ruler(),
+                Err(_) => item.extract()?,
+            };
+            if slf.seen.insert(&ruler) {
+                return Ok(Some(item.into()))
+            }
+        }
+
+        Ok(None)
+    }
+}
@@ -0,0 +1,233 @@
+//! Incremental bookkeeping of the distances between the marks of a ruler.
+
+// `#[new]` constructors expand to non-local impls with pyo3 0.20
+#![cfg_attr(feature = "python", allow(non_local_definitions))]
+
+use std::collections::HashMap;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+use crate::{GInt, dist};
+use super::{GolombRuler, Ruler};
+
+/// Distances below this bound are counted in a vector indexed by distance, longer ones in a map,
+/// so that rulers with huge marks don't allocate a vector as long as themselves
+const DENSE_DISTANCES: usize = 1 << 16;
+
+/// A set of marks, along with the number of times each distance appears between them.
+///
+/// Adding or removing a mark costs O(k) for k marks, after which the number of collisions and the golomb
+/// property are known in O(1), so searches can try marks one at a time without recomputing every distance.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "python", pyclass)]
+pub struct DistanceSet {
+    /// Sorted marks
+    marks: Vec<GInt>,
+    /// Multiplicity of each distance below `DENSE_DISTANCES`, indexed by distance
+    counts: Vec<usize>,
+    /// Multiplicity of each longer distance, without zeros
+    sparse: HashMap<GInt, usize>,
+    /// Number of pairs of marks whose distance was already used by another pair
+    collisions: usize,
+}
+
+impl DistanceSet {
+
+    /// Create a set with the marks `marks`. Duplicate marks are ignored.
+    pub fn from_marks(marks: &[GInt]) -> Self {
+        let mut set = DistanceSet::default();
+        marks.iter().for_each(|m| { set.add_mark(*m); });
+        set
+    }
+
+    /// Our marks, sorted
+    pub fn marks(&self) -> &[GInt] {
+        &self.marks
+    }
+
+    /// Multiplicity of every short distance, indexed by distance. Distances past the end don't appear.
+    ///
+    /// Distances of `2^16` and more are left out, see [`DistanceSet::multiplicities`].
+    pub fn counts(&self) -> &[usize] {
+        &self.counts
+    }
+
+    /// Every distance appearing between two marks, along with its multiplicity, in no particular order
+    pub fn multiplicities(&self) -> impl Iterator<Item = (GInt, usize)> + '_ {
+        let dense = self.counts.iter().enumerate().filter(|(_, c)| **c > 0).map(|(d, c)| (d as GInt, *c));
+        dense.chain(self.sparse.iter().map(|(d, c)| (*d, *c)))
+    }
+
+    pub fn from_ruler(ruler: &Ruler) -> DistanceSet {
+        DistanceSet::from(ruler)
+    }
+
+    /// Remove every mark, keeping our buffers around
+    pub fn clear(&mut self) {
+        self.marks.clear();
+        self.counts.iter_mut().for_each(|c| *c = 0);
+        self.sparse.clear();
+        self.collisions = 0;
+    }
+
+    /// The multiplicity of the distance `d`, which the caller is about to update
+    fn count_mut<'a>(counts: &'a mut Vec<usize>, sparse: &'a mut HashMap<GInt, usize>, d: GInt) -> &'a mut usize {
+        match usize::try_from(d) {
+            Ok(d) if d < DENSE_DISTANCES => {
+                if d >= counts.len() {
+                    counts.resize(d + 1, 0);
+                }
+                &mut counts[d]
+            },
+            _ => sparse.entry(d).or_insert(0),
+        }
+    }
+}
+
+#[cfg_attr(feature = "python", pymethods)]
+impl DistanceSet {
+
+    #[cfg(feature = "python")]
+    #[new]
+    #[pyo3(signature = (marks=Vec::new()))]
+    fn py_new(marks: Vec<GInt>) -> Self {
+        DistanceSet::from_marks(&marks)
+    }
+
+    #[cfg(feature = "python")]
+    fn __repr__(&self) -> String {
+        format!("DistanceSet({:?})", self.marks)
+    }
+
+    #[cfg(feature = "python")]
+    fn __len__(&self) -> usize {
+        self.marks.len()
+    }
+
+    #[cfg(feature = "python")]
+    fn __contains__(&self, p: GInt) -> bool {
+        self.contains(p)
+    }
+
+    /// Add the mark `p`, returning false if it was already present.
+    pub fn add_mark(&mut self, p: GInt) -> bool {
+
+        let idx = match self.marks.binary_search(&p) {
+            Ok(_) => return false,
+            Err(idx) => idx,
+        };
+
+        let DistanceSet { marks, counts, sparse, collisions } = self;
+        for m in marks.iter() {
+            let count = DistanceSet::count_mut(counts, sparse, dist(p, *m));
+            if *count > 0 {
+                *collisions += 1;
+            }
+            *count += 1;
+        }
+
+        marks.insert(idx, p);
+        true
+    }
+
+    /// Remove the mark `p`, returning false if it wasn't present.
+    pub fn remove_mark(&mut self, p: GInt) -> bool {
+
+        let idx = match self.marks.binary_search(&p) {
+            Ok(idx) => idx,
+            Err(_) => return false,
+        };
+
+        self.marks.remove(idx);
+
+        let DistanceSet { marks, counts, sparse, collisions } = self;
+        for m in marks.iter() {
+            let d = dist(p, *m);
+            let count = DistanceSet::count_mut(counts, sparse, d);
+            *count -= 1;
+            if *count > 0 {
+                *collisions -= 1;
+            } else if sparse.get(&d) == Some(&0) {
+                sparse.remove(&d);
+            }
+        }
+
+        true
+    }
+
+    pub fn contains(&self, p: GInt) -> bool {
+        self.marks.binary_search(&p).is_ok()
+    }
+
+    /// Number of times the distance `d` appears between two marks
+    pub fn count(&self, d: GInt) -> usize {
+        match usize::try_from(d) {
+            Ok(d) if d < DENSE_DISTANCES => self.counts.get(d).copied().unwrap_or(0),
+            _ => self.sparse.get(&d).copied().unwrap_or(0),
+        }
+    }
+
+    /// Number of pairs of marks that repeat a distance of another pair
+    pub fn collision_count(&self) -> usize {
+        self.collisions
+    }
+
+    /// Check if no distance appears twice
+    pub fn is_golomb(&self) -> bool {
+        self.collisions == 0
+    }
+
+    /// Our marks, sorted
+    #[cfg(feature = "python")]
+    #[pyo3(name = "marks")]
+    fn py_marks(&self) -> Vec<GInt> {
+        self.marks.clone()
+    }
+
+    /// Convert these marks into a ruler, translating them so that the smallest mark is 0.
+    ///
+    /// An empty set gives the ruler `[0]`.
+    pub fn to_ruler(&self) -> Ruler {
+        Ruler::from(self)
+    }
+
+    #[cfg(feature = "python")]
+    #[staticmethod]
+    #[pyo3(name = "from_ruler")]
+    fn py_from_ruler(ruler: &Ruler) -> DistanceSet {
+        DistanceSet::from(ruler)
+    }
+}
+
+/// Distances follow from the marks, and our buffers keep their length when marks are removed
+impl PartialEq for DistanceSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.marks == other.marks
+    }
+}
+
+impl Eq for DistanceSet {}
+
+impl From<&Ruler> for DistanceSet {
+    fn from(ruler: &Ruler) -> Self {
+        let mut set = DistanceSet::from_marks(&ruler.marks);
+        set.add_mark(0);
+        set
+    }
+}
+
+impl From<&GolombRuler> for DistanceSet {
+    fn from(ruler: &GolombRuler) -> Self {
+        DistanceSet::from(ruler.as_ruler())
+    }
+}
+
+impl From<&DistanceSet> for Ruler {
+    fn from(set: &DistanceSet) -> Self {
+        let first = set.marks.first().copied().unwrap_or(0);
+        Ruler {
+            marks: set.marks.iter().skip(1).map(|m| m - first).collect()
+        }
+    }
+}
@@ -1,154 +1,325 @@
+// `#[new]` constructors expand to non-local impls with pyo3 0.20
+#![cfg_attr(feature = "python", allow(non_local_definitions))]
 
-use std::collections::HashSet;
+#[cfg(feature = "python")]
+use std::collections::{BTreeMap, HashSet};
+#[cfg(feature = "python")]
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hash;
+#[cfg(feature = "python")]
+use std::hash::Hasher;
+use std::ops::Deref;
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::pyclass::CompareOp;
+#[cfg(feature = "python")]
+use pyo3::types::{PyDict, PyIterator};
 use crate::{GInt, dist};
-use super::{Ruler, Distance};
+use super::{Distance, DistanceSet, ExtensionError, Ruler};
 
-#[derive(Clone, Debug)]
-#[pyclass]
-pub struct GolombRuler {
-    pub(crate) marks: Vec<GInt>
+/// A [`Ruler`] with the golomb property.
+///
+/// Golomb rulers are only built once the property is checked, with [`GolombRuler::from_marks`] or `TryFrom<Ruler>`.
+/// They dereference to their [`Ruler`], so every read-only method of rulers is available.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "python", pyclass(module = "ogr_rust"))]
+pub struct GolombRuler(Ruler);
+
+impl Deref for GolombRuler {
+    type Target = Ruler;
+
+    fn deref(&self) -> &Ruler {
+        &self.0
+    }
+}
+
+impl AsRef<Ruler> for GolombRuler {
+    fn as_ref(&self) -> &Ruler {
+        &self.0
+    }
+}
+
+impl TryFrom<Ruler> for GolombRuler {
+    type Error = ExtensionError;
+
+    fn try_from(ruler: Ruler) -> Result<Self, Self::Error> {
+        match ruler.is_golomb_ruler() {
+            true => Ok(GolombRuler(ruler)),
+            false => Err(ExtensionError::NotGolomb),
+        }
+    }
+}
+
+impl From<GolombRuler> for Ruler {
+    fn from(ruler: GolombRuler) -> Self {
+        ruler.0
+    }
+}
+
+impl PartialEq<Ruler> for GolombRuler {
+    fn eq(&self, other: &Ruler) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<GolombRuler> for Ruler {
+    fn eq(&self, other: &GolombRuler) -> bool {
+        *self == other.0
+    }
 }
 
 impl GolombRuler {
-    pub fn new(marks: &[GInt]) -> Self {
-        GolombRuler {
-            marks: marks.to_vec()
+
+    /// Wrap `ruler`, which the caller knows to have the golomb property
+    pub(crate) fn new_unchecked(ruler: Ruler) -> Self {
+        debug_assert!(ruler.is_golomb_ruler(), "{} is not a golomb ruler", ruler);
+        GolombRuler(ruler)
+    }
+
+    /// Create a golomb ruler from its marks, in any order. The mark 0 can be given explicitly or left implied.
+    ///
+    /// Fails on negative or repeated marks, and on marks without the golomb property.
+    pub fn from_marks(marks: &[GInt]) -> Result<Self, ExtensionError> {
+        GolombRuler::try_from(Ruler::from_marks(marks)?)
+    }
+
+    pub fn as_ruler(&self) -> &Ruler {
+        &self.0
+    }
+
+    pub fn into_ruler(self) -> Ruler {
+        self.0
+    }
+
+    /// Check if `p` can be added to `distances`, the marks of a golomb ruler.
+    fn check_extension(distances: &DistanceSet, p: GInt) -> Result<(), ExtensionError> {
+
+        let marks = distances.marks();
+
+        let pair = |a: GInt, b: GInt| Distance { lhs: GInt::min(a, b), rhs: GInt::max(a, b), dist: dist(a, b) };
+
+        if p < 0 {
+            return Err(ExtensionError::NegativeMark(p))
+        }
+
+        if marks.binary_search(&p).is_ok() {
+            return Err(ExtensionError::DuplicateMark(p))
+        }
+
+        // A distance to p is already used by two of our marks
+        for m in marks {
+            let d = dist(p, *m);
+            if distances.count(d) > 0 {
+                let (lhs, rhs) = marks.iter()
+                    .flat_map(|lhs| marks.iter().map(move |rhs| (*lhs, *rhs)))
+                    .find(|(lhs, rhs)| rhs - lhs == d)
+                    .unwrap();
+                return Err(ExtensionError::DistanceClash { mark: p, first: pair(lhs, rhs), second: pair(*m, p) })
+            }
+        }
+
+        // p is right in the middle of two marks
+        for a in marks.iter().take_while(|a| **a < p) {
+            if marks.binary_search(&(2 * p - a)).is_ok() {
+                return Err(ExtensionError::DistanceClash { mark: p, first: pair(*a, p), second: pair(p, 2 * p - a) })
+            }
         }
+
+        Ok(())
     }
 }
 
 impl std::fmt::Display for GolombRuler {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-
-        // prepend a zero to our vector
-        let mut out: Vec<GInt> = vec![0];
-        out.append(&mut self.marks.clone());
-        write!(f, "{:?}", out)
+        self.0.fmt(f)
     }
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl GolombRuler {
 
+    /// Create a golomb ruler from its marks, in any order, with 0 given explicitly or left implied.
+    ///
+    /// Raises a `ValueError` for negative or repeated marks, and for marks without the golomb property.
+    #[new]
+    fn py_new(marks: Vec<GInt>) -> PyResult<Self> {
+        Ok(GolombRuler::from_marks(&marks)?)
+    }
+
     fn __repr__(&self) -> String {
         self.to_string()
     }
 
-    pub fn order(&self) -> usize {
-        self.marks.len() + 1
+    fn __richcmp__(&self, other: PyRef<GolombRuler>, op: CompareOp) -> bool {
+        op.matches(self.cmp(&other))
     }
 
-    pub fn length(&self) -> GInt {
-        if self.marks.is_empty() {
-            return 0
-        }
-        self.marks[self.marks.len() - 1]
+    fn __hash__(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
     }
 
-    /// Check if the data in `self.marks` actual admits the Golomb Property.
-    pub fn is_golomb_ruler(&self) -> bool {
-        Ruler::is_golomb_ruler(&self.marks)
+    /// The number of marks, 0 included
+    fn __len__(&self) -> usize {
+        self.order()
     }
 
-    /// Return the marks as a set
-    pub fn as_set(&self) -> HashSet<GInt> {
-        let mut out = HashSet::<GInt>::new();
+    fn __iter__(&self, py: Python<'_>) -> PyResult<Py<PyIterator>> {
+        self.0.__iter__(py)
+    }
 
-        for m in &self.marks {
-            out.insert(*m);
-        }
+    /// The mark at position `idx`, where 0 is the first mark. Negative positions count from the end.
+    fn __getitem__(&self, idx: isize) -> PyResult<GInt> {
+        self.0.__getitem__(idx)
+    }
 
-        out
+    fn __contains__(&self, mark: GInt) -> bool {
+        self.0.__contains__(mark)
     }
 
+    /// Rebuild rulers from their marks when unpickling
+    fn __reduce__(&self, py: Python<'_>) -> (PyObject, (Vec<GInt>,)) {
+        (py.get_type::<GolombRuler>().into(), (self.to_list(),))
+    }
 
-    /// Used in enumeration algorithms. Check if the first order of distances are unique.
-    ///
-    /// Consider the following array
-    ///
-    /// `[1, 3, 4]`
-    ///
-    /// 0th order: `[1, 3, 4]`
-    /// 1st order: `[2, 3]`
-    /// 2nd order: `[1]`
+    #[pyo3(name = "to_list")]
+    fn py_to_list(&self) -> Vec<GInt> {
+        self.to_list()
+    }
+
+    /// A dictionary with our `marks`, `order` and `length`
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        self.0.to_dict(py)
+    }
+
+    /// Convert this golomb ruler into a general `Ruler`
+    fn to_ruler(&self) -> Ruler {
+        self.0.clone()
+    }
+
+    #[pyo3(name = "order")]
+    fn py_order(&self) -> usize {
+        self.order()
+    }
+
+    #[pyo3(name = "length")]
+    fn py_length(&self) -> GInt {
+        self.length()
+    }
+
+    /// Always true, golomb rulers are checked when they are built
+    #[pyo3(name = "is_golomb_ruler")]
+    fn py_is_golomb_ruler(&self) -> bool {
+        self.is_golomb_ruler()
+    }
+
+    /// Check if the first order of distances are unique, see `Ruler.is_golomb_ruler_order_1`
+    #[pyo3(name = "is_golomb_ruler_order_1")]
+    fn py_is_golomb_ruler_order_1(&self) -> bool {
+        self.is_golomb_ruler_order_1()
+    }
+}
+
+#[cfg_attr(feature = "python", pymethods)]
+impl GolombRuler {
+
+    /// Return the mirror image of this ruler, obtained by reading its marks from right to left.
     ///
-    /// The idea is to prune our enumeration tree based on a O(2n) or O(n) check; n the number of marks on our ruler
-    /// We can later iterate over the returned rulers to actually filter them.
-    pub fn is_golomb_ruler_order_1(&self) -> bool {
-        // start with the last element of our ruler and subtract every other element
-        let set = self.as_set();
-        let base = self.length(); // 0 is implied, our first element
-
-        for m in &self.marks[0..(self.marks.len() - 1)] {
-            let d = dist(*m, base);
-            if set.contains(&d) {
-                return false;
-            }
-        }
-        true
+    /// The mirror of `[0, 1, 3, 7]` is `[0, 4, 6, 7]`.
+    pub fn mirror(&self) -> GolombRuler {
+        GolombRuler(self.0.mirror())
     }
 
-    pub fn distances(&self) -> Vec<Distance> {
+    /// Return the lexicographically smallest of this ruler and its mirror image.
+    pub fn canonical(&self) -> GolombRuler {
+        GolombRuler(self.0.canonical())
+    }
 
-        let mut out: Vec<Distance> = Vec::new();
+    /// Check if no mark can be added inside `[0, length]` without breaking the golomb property.
+    pub fn is_maximal(&self) -> bool {
+        self.valid_extensions(self.length()).is_empty()
+    }
 
-        for (idx, lhs) in self.marks.iter().enumerate() {
-            out.push(Distance {
-                lhs: 0,
-                rhs: *lhs,
-                dist: *lhs
-            });
-            for rhs in &self.marks[idx + 1..] {
-                out.push(Distance {
-                    lhs: *lhs,
-                    rhs: *rhs,
-                    dist: GInt::abs(rhs - lhs)
-                });
-            }
-        }
+    /// Return every position up to `max_length`, inside or beyond our length, where a new mark keeps the golomb property.
+    pub fn valid_extensions(&self, max_length: GInt) -> Vec<GInt> {
+        let distances = DistanceSet::from(self.as_ruler());
+        (1..=max_length).filter(|p| GolombRuler::check_extension(&distances, *p).is_ok()).collect()
+    }
 
-        out
+    /// Return a copy of this ruler with the new mark `p`, or the reason why `p` breaks the golomb property.
+    pub fn with_mark(&self, p: GInt) -> Result<GolombRuler, ExtensionError> {
+
+        GolombRuler::check_extension(&DistanceSet::from(self.as_ruler()), p)?;
+
+        let mut marks = self.marks.clone();
+        let idx = marks.partition_point(|m| *m < p);
+        marks.insert(idx, p);
+
+        Ok(GolombRuler(Ruler { marks }))
     }
 
-    pub fn to_id(&self) -> Option<usize> {
+    /// Return a copy of this ruler without the mark `p`.
+    ///
+    /// Removing 0 shifts the remaining marks so that our ruler starts at 0 again.
+    pub fn without_mark(&self, p: GInt) -> Result<GolombRuler, ExtensionError> {
 
-        // [0]
-        if self.marks.is_empty() {
-            Some(0)
-        } else if self.length() == 1 {
-        // [0, 1]
-            Some(1)
-        } else if self.length() > 64 {
-            None
-        } else {
-            let mut val = 1 << (self.length() - 1);
-            // println!("Starting value: {}", val);
-            let state = self.to_state();
-            self.marks[0..self.marks.len() - 1].iter().for_each(|m| if state[(*m - 1) as usize] { val += 1 << (m - 1)} );
+        if p == 0 {
+            let first = *self.marks.first().ok_or(ExtensionError::LastMark)?;
+            return Ok(GolombRuler(Ruler { marks: self.marks[1..].iter().map(|m| m - first).collect() }))
+        }
 
-            Some(val)
+        match self.marks.iter().position(|m| *m == p) {
+            Some(idx) => {
+                let mut marks = self.marks.clone();
+                marks.remove(idx);
+                Ok(GolombRuler(Ruler { marks }))
+            },
+            None => Err(ExtensionError::MissingMark(p))
         }
     }
+}
 
+#[cfg(feature = "python")]
+#[pymethods]
+impl GolombRuler {
 
-    pub fn to_state(&self) -> Vec<bool> {
+    /// Return the marks as a set
+    #[pyo3(name = "as_set")]
+    fn py_as_set(&self) -> HashSet<GInt> {
+        self.as_set()
+    }
 
-        let l = self.length();
+    #[pyo3(name = "distances")]
+    fn py_distances(&self) -> Vec<Distance> {
+        self.distances()
+    }
 
-        if self.order() == 1 || self.length() == 1 {
-            return vec![]
-        }
+    #[pyo3(name = "difference_triangle", signature = (fill=None))]
+    fn py_difference_triangle(&self, fill: Option<GInt>) -> Vec<Vec<GInt>> {
+        self.0.py_difference_triangle(fill)
+    }
 
-        // Initialize with all false, dropping the 0 and length
-        let mut state = vec![false; (l - 1) as usize];
+    /// The pair of marks measuring every distance
+    #[pyo3(name = "pairs_by_distance")]
+    fn py_pairs_by_distance(&self) -> BTreeMap<GInt, (GInt, GInt)> {
+        self.pairs_by_distance().into_iter().map(|(d, pairs)| (d, pairs[0])).collect()
+    }
 
-        // Drop the 0
-        for m in &self.marks[0..self.marks.len() - 1] {
-            state[*m as usize - 1] = true;
-        }
+    #[pyo3(name = "format_triangle")]
+    fn py_format_triangle(&self) -> String {
+        self.format_triangle()
+    }
 
-        state
+    #[pyo3(name = "to_id")]
+    fn py_to_id(&self) -> PyResult<usize> {
+        Ok(self.to_id()?)
     }
 
-}
\ No newline at end of file
+    #[pyo3(name = "to_state")]
+    fn py_to_state(&self) -> Vec<bool> {
+        self.to_state()
+    }
+}
@@ -1,107 +1,170 @@
 //! Define the core Ruler structure and some associated functions.
 
+mod beam;
+mod canonical;
+mod distance_set;
 mod golomb_ruler;
+mod notation;
+mod rng;
 mod ruler;
+mod sidon;
+mod turnpike;
 
-use std::collections::HashSet;
+#[cfg(feature = "python")]
+use std::collections::hash_map::DefaultHasher;
+#[cfg(feature = "python")]
+use std::hash::{Hash, Hasher};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::pyclass::CompareOp;
+use thiserror::Error;
 
-use pyo3::{exceptions, prelude::*};
-use pyo3::create_exception;
+use crate::error::{OgrError, Result, check_order};
 
 pub use ruler::Ruler;
 pub use golomb_ruler::GolombRuler;
+pub use distance_set::DistanceSet;
+pub use notation::{RulerFormat, ParseRulerError};
+pub use sidon::{SidonSequence, StepRule};
+pub use turnpike::{reconstruct_rulers, reconstruct_beltway};
+pub use beam::{BeamSearch, BeamScore, lookahead_score};
+#[cfg(feature = "python")]
+pub use beam::beam_search_ruler;
+pub use canonical::{Canonical, Dedup, dedup_rulers};
+#[cfg(feature = "python")]
+pub use canonical::RulerDedupIterator;
+#[cfg(feature = "python")]
+pub(crate) use canonical::py_dedup_rulers;
+pub(crate) use rng::SplitMix64;
+
+
+/// The distance `dist` between the marks `lhs < rhs` of a ruler
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "python", pyclass(get_all))]
+pub struct Distance {
+    pub lhs: GInt,
+    pub rhs: GInt,
+    pub dist: GInt,
+}
 
-create_exception!(ogr_rust, MyError, pyo3::exceptions::PyException);
-
+#[cfg(feature = "python")]
+#[pymethods]
+impl Distance {
+    fn __repr__(&self) -> String {
+        format!("Distance(lhs={}, rhs={}, dist={})", self.lhs, self.rhs, self.dist)
+    }
 
+    fn __richcmp__(&self, other: PyRef<Distance>, op: CompareOp) -> bool {
+        op.matches(self.cmp(&other))
+    }
 
-#[derive(Debug, Clone)]
-#[pyclass]
-struct Distance {
-    lhs: GInt,
-    rhs: GInt,
-    dist: GInt,
+    fn __hash__(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
 }
 
-pub(crate) type GInt = i128;
-
-pub(crate) fn dist(a: GInt, b: GInt) -> GInt{
-    GInt::abs(a - b)
+/// A distance that appears more than once in a ruler, with every pair of marks that produces it
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "python", pyclass(get_all))]
+pub struct Collision {
+    pub distance: GInt,
+    pub pairs: Vec<(GInt, GInt)>,
 }
 
-/// Compute the pairwise distances of `sequence` and return the results in a set.
-fn compute_distances(sequence: &[i128]) -> HashSet<i128> {
+#[cfg(feature = "python")]
+#[pymethods]
+impl Collision {
+    fn __repr__(&self) -> String {
+        format!("Collision(distance={}, pairs={:?})", self.distance, self.pairs)
+    }
+}
 
-    let mut distances: HashSet<i128> = HashSet::new();
-    let n = sequence.len();
+/// Why a mark can't be added to or removed from a [`GolombRuler`]
+#[derive(Error, Debug, Clone)]
+pub enum ExtensionError {
+    #[error("Marks can't be negative, got {0}")]
+    NegativeMark(GInt),
+    #[error("{0} is already a mark")]
+    DuplicateMark(GInt),
+    #[error("{0} is not a mark")]
+    MissingMark(GInt),
+    #[error("The only mark of a ruler can't be removed")]
+    LastMark,
+    #[error("The ruler doesn't have the golomb property")]
+    NotGolomb,
+    #[error("Adding {mark} repeats the distance {}: between {} and {}, and between {} and {}",
+        first.dist, first.lhs, first.rhs, second.lhs, second.rhs)]
+    DistanceClash {
+        mark: GInt,
+        first: Distance,
+        second: Distance,
+    },
+}
 
-    for (lhs_index, lhs) in sequence.iter().enumerate() {
-        for rhs in &sequence[(lhs_index + 1)..n] {
-            distances.insert(dist(*lhs, *rhs));
-        }
+#[cfg(feature = "python")]
+impl From<ExtensionError> for PyErr {
+    fn from(err: ExtensionError) -> PyErr {
+        OgrError::from(err).into()
     }
+}
+
+/// The type of the marks of a ruler
+pub type GInt = i128;
 
-    distances
+pub(crate) fn dist(a: GInt, b: GInt) -> GInt{
+    GInt::abs(a - b)
 }
 
 /// Naively generate a new golomb ruler with `order` marks
-#[pyfunction]
-pub(crate) fn generate_golomb_ruler_naive(order: u32) -> PyResult<Vec<i128>> {
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn generate_golomb_ruler_naive(order: u32) -> Result<Vec<i128>> {
+
+    check_order(order as usize)?;
 
     match order {
-        0 => Err(exceptions::PyValueError::new_err("Order must be greater than 0!")),
         1 => Ok(vec![0i128]),
         _ => {
-            let mut prev = generate_golomb_ruler_naive(order - 1).unwrap();
-            let next = i128::pow(2, order - 1) - 1;
+            let mut prev = generate_golomb_ruler_naive(order - 1)?;
+            let next = i128::checked_pow(2, order - 1).ok_or_else(|| OgrError::Overflow(format!("2^{}", order - 1)))? - 1;
             prev.push(next);
             Ok(prev)
         }
     }
 }
 
-/// Utility function used to check if a candidate should be accepted.
-fn should_accept_candidate(candidate: i128, distances: &HashSet<i128>, prev: &[i128], order: u32) -> bool {
-    for i in 0usize..(order - 1) as usize {
-        let gap = dist(candidate, prev[i]);
-        if distances.contains(&gap) {
-            return false
-        };
-    }
-
-    true
-}
-
 /// Slightly improved version of our Golomb Ruler function
-#[pyfunction]
-pub(crate) fn generate_golomb_ruler_improved(order: u32) -> PyResult<Vec<i128>> {
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn generate_golomb_ruler_improved(order: u32) -> Result<Vec<i128>> {
+
+    check_order(order as usize)?;
 
     match order {
-        0 => Err(exceptions::PyValueError::new_err("Order must be greater than 0!")),
         1 => Ok(vec![0i128]),
         2 => Ok(vec![0i128, 1i128]),
         3 => Ok(vec![0i128, 1i128, 3i128]),
         _ => {
 
-            let mut prev = generate_golomb_ruler_improved(order - 1).unwrap();
-            let prev_last = prev.last().unwrap();
-
-            let distances = compute_distances(&prev);
-            let candidate_upper_bound = 2 * prev_last + 1;
+            let prev = generate_golomb_ruler_improved(order - 1)?;
+            let prev_last = prev[prev.len() - 1];
 
-            for c in *prev_last..candidate_upper_bound + 1 {
-                if prev.contains(&c) {
-                    continue
-                };
+            let mut distances = DistanceSet::from_marks(&prev);
+            // Every distance to this mark is longer than our ruler, so it always keeps the golomb property
+            let candidate_upper_bound = prev_last.checked_mul(2).and_then(|m| m.checked_add(1))
+                .ok_or_else(|| OgrError::Overflow(format!("2 * {} + 1", prev_last)))?;
 
-                if should_accept_candidate(c, &distances, &prev, order) {
-                    prev.push(c);
-                    prev.sort();
-                    return Ok(prev);
+            for c in prev_last + 1..candidate_upper_bound {
+                distances.add_mark(c);
+                if distances.is_golomb() {
+                    return Ok(distances.marks().to_vec());
                 };
+                distances.remove_mark(c);
             };
 
-            Err(exceptions::PyBaseException::new_err("Implementation Error on behalf of the programmer!"))
+            distances.add_mark(candidate_upper_bound);
+            Ok(distances.marks().to_vec())
         }
     }
 }
@@ -0,0 +1,260 @@
+//! Parse and format rulers in the notations found in papers and on distributed.net.
+//!
+//! * Marks separated by spaces and/or commas: `0 1 4 9 11`
+//! * Bracketed lists of marks: `[0, 1, 4, 9, 11]`
+//! * Gaps between consecutive marks: `1-3-5-2`
+//!
+//! Mark lists may be unsorted and don't have to start at 0: they are translated so that their smallest mark is 0.
+//! A hyphen after a space and before a digit is the sign of a mark, so `3 -2` is a list of marks.
+//! Text without any hyphen between gaps, like the gaps `11` of `[0, 11]`, is read as marks unless parsed with [`Ruler::parse_as`].
+
+use std::str::FromStr;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use thiserror::Error;
+
+use crate::GInt;
+#[cfg(feature = "python")]
+use crate::error::OgrError;
+use super::{DistanceSet, GolombRuler, Ruler};
+
+/// Notation of a ruler, see the [module documentation](self)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RulerFormat {
+    /// `0 1 4 9 11`
+    Marks,
+    /// `1-3-5-2`
+    Gaps,
+    /// `[0, 1, 4, 9, 11]`
+    Brackets,
+}
+
+impl FromStr for RulerFormat {
+    type Err = ParseRulerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "marks" => Ok(RulerFormat::Marks),
+            "gaps" => Ok(RulerFormat::Gaps),
+            "brackets" => Ok(RulerFormat::Brackets),
+            _ => Err(ParseRulerError::UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+/// Why some text isn't a golomb ruler
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ParseRulerError {
+    #[error("No marks to parse")]
+    Empty,
+    #[error("'{0}' is not an integer")]
+    InvalidNumber(String),
+    #[error("Gaps must be positive, got {0}")]
+    InvalidGap(GInt),
+    #[error("The gaps add up past the largest supported mark")]
+    GapOverflow,
+    #[error("{0} appears more than once")]
+    DuplicateMark(GInt),
+    #[error("The marks {0:?} don't have the golomb property")]
+    NotGolomb(Vec<GInt>),
+    #[error("Unknown format '{0}', expected one of 'marks', 'gaps' or 'brackets'")]
+    UnknownFormat(String),
+}
+
+#[cfg(feature = "python")]
+impl From<ParseRulerError> for PyErr {
+    fn from(err: ParseRulerError) -> PyErr {
+        OgrError::from(err).into()
+    }
+}
+
+fn parse_number(s: &str) -> Result<GInt, ParseRulerError> {
+    s.trim().parse().map_err(|_| ParseRulerError::InvalidNumber(s.trim().to_string()))
+}
+
+/// Split `text` at the hyphens between gaps.
+///
+/// A hyphen at the start of `text` or after a space or a comma, and followed by a digit, is the sign of a mark instead.
+fn split_gaps(text: &str) -> Vec<&str> {
+
+    let mut pieces = vec![];
+    let mut start = 0;
+    let mut previous: Option<char> = None;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let is_sign = previous.is_none_or(|p| p.is_whitespace() || p == ',')
+            && chars.peek().is_some_and(|(_, n)| n.is_ascii_digit());
+        if c == '-' && !is_sign {
+            pieces.push(&text[start..i]);
+            start = i + 1;
+        }
+        previous = Some(c);
+    }
+
+    pieces.push(&text[start..]);
+    pieces
+}
+
+/// Parse the marks written in `text`, in the notation `format` or in any of our notations if it's `None`.
+///
+/// Without a format, text with no hyphen between gaps is a list of marks, so a single gap must be parsed as [`RulerFormat::Gaps`].
+fn parse_marks(text: &str, format: Option<RulerFormat>) -> Result<Vec<GInt>, ParseRulerError> {
+
+    let text = text.trim();
+    let text = text.strip_prefix('[').and_then(|t| t.strip_suffix(']')).unwrap_or(text).trim();
+    let gaps = split_gaps(text);
+
+    let is_gaps = match format {
+        Some(format) => format == RulerFormat::Gaps,
+        None => gaps.len() > 1,
+    };
+
+    if is_gaps {
+        // The ruler [0] has no gaps
+        let mut marks = vec![0];
+        if text.is_empty() {
+            return Ok(marks)
+        }
+        for gap in gaps {
+            let gap = parse_number(gap)?;
+            if gap <= 0 {
+                return Err(ParseRulerError::InvalidGap(gap))
+            }
+            let mark = marks[marks.len() - 1].checked_add(gap).ok_or(ParseRulerError::GapOverflow)?;
+            marks.push(mark);
+        }
+        return Ok(marks)
+    }
+
+    if text.is_empty() {
+        return Err(ParseRulerError::Empty)
+    }
+
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(parse_number)
+        .collect()
+}
+
+/// Build the ruler with the parsed `marks`, translated so that they start at 0
+fn ruler_from_marks(mut marks: Vec<GInt>) -> Result<Ruler, ParseRulerError> {
+
+    marks.sort();
+
+    if let Some(w) = marks.windows(2).find(|w| w[0] == w[1]) {
+        return Err(ParseRulerError::DuplicateMark(w[0]))
+    }
+
+    Ok(Ruler::from(&DistanceSet::from_marks(&marks)))
+}
+
+fn golomb_from_ruler(ruler: Ruler) -> Result<GolombRuler, ParseRulerError> {
+
+    if !ruler.is_golomb_ruler() {
+        return Err(ParseRulerError::NotGolomb(ruler.to_list()))
+    }
+
+    Ok(GolombRuler::new_unchecked(ruler))
+}
+
+impl FromStr for Ruler {
+    type Err = ParseRulerError;
+
+    /// Parse a ruler from any of the notations of the [`notation`](self) module. Repeated distances are allowed.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        ruler_from_marks(parse_marks(text, None)?)
+    }
+}
+
+impl FromStr for GolombRuler {
+    type Err = ParseRulerError;
+
+    /// Parse a golomb ruler from any of the notations of the [`notation`](self) module.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        golomb_from_ruler(text.parse()?)
+    }
+}
+
+impl GolombRuler {
+
+    /// Parse a golomb ruler written in the notation `format`, see [`Ruler::parse_as`].
+    pub fn parse_as(text: &str, format: RulerFormat) -> Result<GolombRuler, ParseRulerError> {
+        golomb_from_ruler(Ruler::parse_as(text, format)?)
+    }
+}
+
+impl Ruler {
+
+    /// Parse a ruler written in the notation `format`. Repeated distances are allowed.
+    ///
+    /// Unlike [`str::parse`], this reads a single gap such as `11` as the ruler `[0, 11]`, and an empty string as the gaps of `[0]`.
+    /// [`RulerFormat::Marks`] and [`RulerFormat::Brackets`] both accept a list of marks with or without brackets.
+    pub fn parse_as(text: &str, format: RulerFormat) -> Result<Ruler, ParseRulerError> {
+        ruler_from_marks(parse_marks(text, Some(format))?)
+    }
+
+    /// Write this ruler in the notation `format`. The gaps of the ruler `[0]` are an empty string.
+    pub fn format(&self, format: RulerFormat) -> String {
+
+        let marks = || std::iter::once(0).chain(self.marks.iter().copied()).map(|m| m.to_string());
+
+        match format {
+            RulerFormat::Marks => marks().collect::<Vec<_>>().join(" "),
+            RulerFormat::Brackets => format!("[{}]", marks().collect::<Vec<_>>().join(", ")),
+            RulerFormat::Gaps => {
+                let gaps = std::iter::once(&0).chain(&self.marks).zip(&self.marks).map(|(a, b)| (b - a).to_string());
+                gaps.collect::<Vec<_>>().join("-")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl Ruler {
+
+    /// Parse a ruler from marks (`"0 1 2 4"`, `"[0, 1, 2, 4]"`) or gaps (`"1-1-2"`).
+    ///
+    /// Mark lists may be unsorted and are translated so that they start at 0.
+    /// Pass `style` (`"marks"`, `"gaps"` or `"brackets"`) to read the text in that notation only, e.g. the single gap `"11"`.
+    #[staticmethod]
+    #[pyo3(name = "parse", signature = (text, style=None))]
+    fn py_parse(text: &str, style: Option<&str>) -> PyResult<Ruler> {
+        match style {
+            Some(style) => Ok(Ruler::parse_as(text, style.parse()?)?),
+            None => Ok(text.parse()?),
+        }
+    }
+
+    /// Write this ruler as `"marks"`, `"gaps"` or `"brackets"`
+    #[pyo3(name = "format", signature = (style="marks"))]
+    fn py_format(&self, style: &str) -> PyResult<String> {
+        Ok(self.format(style.parse()?))
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl GolombRuler {
+
+    /// Parse a golomb ruler from marks (`"0 1 4 9 11"`, `"[0, 1, 4, 9, 11]"`) or gaps (`"1-3-5-2"`).
+    ///
+    /// Mark lists may be unsorted and are translated so that they start at 0.
+    /// Pass `style` (`"marks"`, `"gaps"` or `"brackets"`) to read the text in that notation only, e.g. the single gap `"11"`.
+    #[staticmethod]
+    #[pyo3(name = "parse", signature = (text, style=None))]
+    fn py_parse(text: &str, style: Option<&str>) -> PyResult<GolombRuler> {
+        match style {
+            Some(style) => Ok(GolombRuler::parse_as(text, style.parse()?)?),
+            None => Ok(text.parse()?),
+        }
+    }
+
+    /// Write this ruler as `"marks"`, `"gaps"` or `"brackets"`
+    #[pyo3(name = "format", signature = (style="marks"))]
+    fn py_format(&self, style: &str) -> PyResult<String> {
+        Ok(self.format(style.parse()?))
+    }
+}
@@ -1,45 +1,430 @@
+// `#[new]` constructors expand to non-local impls with pyo3 0.20
+#![cfg_attr(feature = "python", allow(non_local_definitions))]
 
-use pyo3::pyclass;
+use std::collections::{BTreeMap, HashSet};
+#[cfg(feature = "python")]
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hash;
+#[cfg(feature = "python")]
+use std::hash::Hasher;
+#[cfg(feature = "python")]
+use pyo3::exceptions;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::pyclass::CompareOp;
+#[cfg(feature = "python")]
+use pyo3::types::{PyDict, PyIterator, PyList};
 use crate::{GInt, dist};
-use std::collections::HashSet;
+use crate::error::{OgrError, Result};
+use super::{Collision, Distance, DistanceSet, ExtensionError};
+#[cfg(feature = "python")]
+use super::GolombRuler;
 
-/// Implementation of a Golomb Ruler.
-#[pyclass]
+/// A set of marks starting at 0, which doesn't need to have the golomb property.
+///
+/// The mark 0 is implied: `marks` holds every other mark in increasing order, ending with the length of the ruler.
+/// Rulers compare by their marks. Use [`Ruler::canonical`] or [`Canonical`](super::Canonical)
+/// to consider a ruler and its mirror image equal.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "python", pyclass(module = "ogr_rust"))]
 pub struct Ruler {
+    pub(crate) marks: Vec<GInt>
+}
+
+impl Ruler {
+    /// Create a ruler from its marks, sorted and without 0
+    pub fn new(marks: &[GInt]) -> Self {
+        Ruler {
+            marks: marks.to_vec()
+        }
+    }
 
+    /// Create a ruler from its marks, in any order. The mark 0 can be given explicitly or left implied.
+    ///
+    /// Fails on negative or repeated marks.
+    pub fn from_marks(marks: &[GInt]) -> Result<Self, ExtensionError> {
+
+        let mut marks = marks.to_vec();
+        marks.sort();
+
+        if let Some(m) = marks.iter().find(|m| **m < 0) {
+            return Err(ExtensionError::NegativeMark(*m))
+        }
+        if let Some(w) = marks.windows(2).find(|w| w[0] == w[1]) {
+            return Err(ExtensionError::DuplicateMark(w[0]))
+        }
+
+        if marks.first() == Some(&0) {
+            marks.remove(0);
+        }
+
+        Ok(Ruler { marks })
+    }
+
+    /// Every mark of this ruler, 0 included
+    pub fn to_list(&self) -> Vec<GInt> {
+        std::iter::once(0).chain(self.marks.iter().copied()).collect()
+    }
+
+    /// Return the difference triangle of this ruler: `triangle[k - 1]` holds the distances between marks `k` apart.
+    ///
+    /// The first row holds the gaps between consecutive marks, and the last row the length.
+    /// The triangle of `[0, 1, 4, 9, 11]` is `[[1, 3, 5, 2], [4, 8, 7], [9, 10], [11]]`.
+    pub fn difference_triangle(&self) -> Vec<Vec<GInt>> {
+        let marks = self.to_list();
+        (1..marks.len()).map(|k| marks.windows(k + 1).map(|w| w[k] - w[0]).collect()).collect()
+    }
+}
+
+/// Try to make `distances` golomb by removing at most `budget` marks, pushing them onto `removed`.
+///
+/// One of the four marks of any two colliding pairs has to go, so we only branch on those.
+fn repair(distances: &mut DistanceSet, budget: usize, removed: &mut Vec<GInt>) -> bool {
+
+    if distances.is_golomb() {
+        return true
+    }
+
+    if budget == 0 {
+        return false
+    }
+
+    let marks = distances.marks();
+    let d = distances.multiplicities().find(|(_, c)| *c > 1).unwrap().0;
+    let mut candidates: Vec<GInt> = marks.iter()
+        .filter(|lhs| distances.contains(**lhs + d))
+        .take(2)
+        .flat_map(|lhs| [*lhs, lhs + d])
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+
+    for m in candidates {
+        distances.remove_mark(m);
+        removed.push(m);
+
+        if repair(distances, budget - 1, removed) {
+            return true
+        }
+
+        removed.pop();
+        distances.add_mark(m);
+    }
+
+    false
+}
+
+impl AsRef<Ruler> for Ruler {
+    fn as_ref(&self) -> &Ruler {
+        self
+    }
 }
 
-// #[pymethods]
+impl std::fmt::Display for Ruler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.to_list())
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
 impl Ruler {
 
-    /// Verify if a sequence of integers satisfies the golomb property.
-    // #[staticmethod]
-    pub fn is_golomb_ruler(sequence: &[GInt]) -> bool {
-
-        let mut distances: HashSet<GInt> = HashSet::new();
-        let n = sequence.len();
-
-        for (lhs_index, lhs) in sequence.iter().enumerate() {
-            // for rhs in sequence[]
-            let diff = *lhs;
-            if distances.contains(&diff) {
-                return false
-            } else {
-                distances.insert(diff)
-            };
-
-            for rhs in &sequence[(lhs_index + 1)..n] {
-                let diff = dist(*lhs, *rhs);
-                if distances.contains(&diff) {
-                    return false
-                } else {
-                    distances.insert(diff)
-                };
+    /// Create a ruler from its marks, in any order, with 0 given explicitly or left implied.
+    ///
+    /// Raises a `ValueError` for negative or repeated marks.
+    #[new]
+    fn py_new(marks: Vec<GInt>) -> PyResult<Self> {
+        Ok(Ruler::from_marks(&marks)?)
+    }
+
+    fn __repr__(&self) -> String {
+        self.to_string()
+    }
+
+    fn __richcmp__(&self, other: PyRef<Ruler>, op: CompareOp) -> bool {
+        op.matches(self.cmp(&other))
+    }
+
+    fn __hash__(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// The number of marks, 0 included
+    fn __len__(&self) -> usize {
+        self.order()
+    }
+
+    pub(crate) fn __iter__(&self, py: Python<'_>) -> PyResult<Py<PyIterator>> {
+        let marks = PyList::new(py, self.to_list());
+        Ok(PyIterator::from_object(marks)?.into())
+    }
+
+    /// The mark at position `idx`, where 0 is the first mark. Negative positions count from the end.
+    pub(crate) fn __getitem__(&self, idx: isize) -> PyResult<GInt> {
+        let order = self.order() as isize;
+        let pos = if idx < 0 { idx + order } else { idx };
+        match pos {
+            0 => Ok(0),
+            1.. if pos < order => Ok(self.marks[pos as usize - 1]),
+            _ => Err(exceptions::PyIndexError::new_err(format!("Mark index {} out of range for a ruler with {} marks", idx, order))),
+        }
+    }
+
+    pub(crate) fn __contains__(&self, mark: GInt) -> bool {
+        mark == 0 || self.marks.binary_search(&mark).is_ok()
+    }
+
+    /// Rebuild rulers from their marks when unpickling
+    fn __reduce__(&self, py: Python<'_>) -> (PyObject, (Vec<GInt>,)) {
+        (py.get_type::<Ruler>().into(), (self.to_list(),))
+    }
+
+    #[pyo3(name = "to_list")]
+    fn py_to_list(&self) -> Vec<GInt> {
+        self.to_list()
+    }
+
+    /// A dictionary with our `marks`, `order` and `length`
+    pub(crate) fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let dict = PyDict::new(py);
+        dict.set_item("marks", self.to_list())?;
+        dict.set_item("order", self.order())?;
+        dict.set_item("length", self.length())?;
+        Ok(dict)
+    }
+
+    /// Return the difference triangle, where row `k - 1` holds the distances between marks `k` apart.
+    ///
+    /// With `fill`, rows are padded with `fill` to the same length, so that `numpy.array` gives a 2D array.
+    #[pyo3(name = "difference_triangle", signature = (fill=None))]
+    pub(crate) fn py_difference_triangle(&self, fill: Option<GInt>) -> Vec<Vec<GInt>> {
+        let mut triangle = self.difference_triangle();
+        if let Some(fill) = fill {
+            triangle.iter_mut().for_each(|row| row.resize(self.order() - 1, fill));
+        }
+        triangle
+    }
+
+    /// Convert this ruler into a `GolombRuler`, raising a `ValueError` if it doesn't have the golomb property
+    fn to_golomb(&self) -> PyResult<GolombRuler> {
+        Ok(GolombRuler::try_from(self.clone())?)
+    }
+}
+
+#[cfg_attr(feature = "python", pymethods)]
+impl Ruler {
+
+    pub fn order(&self) -> usize {
+        self.marks.len() + 1
+    }
+
+    pub fn length(&self) -> GInt {
+        if self.marks.is_empty() {
+            return 0
+        }
+        self.marks[self.marks.len() - 1]
+    }
+
+    /// Check if the data in `self.marks` actual admits the Golomb Property.
+    pub fn is_golomb_ruler(&self) -> bool {
+        // A repeated mark repeats every one of its distances
+        let distances = DistanceSet::from(self);
+        distances.is_golomb() && distances.marks().len() == self.order()
+    }
+
+    /// Return the mirror image of this ruler, obtained by reading its marks from right to left.
+    ///
+    /// The mirror of `[0, 1, 3, 7]` is `[0, 4, 6, 7]`.
+    pub fn mirror(&self) -> Ruler {
+        let length = self.length();
+        let marks = self.marks.iter().rev().skip(1).map(|m| length - m).chain(std::iter::once(length));
+        Ruler {
+            marks: if self.marks.is_empty() { vec![] } else { marks.collect() }
+        }
+    }
+
+    /// Return the lexicographically smallest of this ruler and its mirror image.
+    ///
+    /// A ruler and its mirror share the same canonical form: `[0, 1, 3, 7]` and `[0, 4, 6, 7]` both give `[0, 1, 3, 7]`.
+    pub fn canonical(&self) -> Ruler {
+        Ruler::min(self.clone(), self.mirror())
+    }
+
+    /// Check if no distance appears more than `lambda_` times. A multiplicity of 1 is the golomb property.
+    pub fn is_near_golomb(&self, lambda_: usize) -> bool {
+        let distances = DistanceSet::from(self);
+        distances.multiplicities().all(|(_, c)| c <= lambda_) && distances.marks().len() == self.order()
+    }
+
+    /// Return every distance that appears more than once, along with the pairs of marks producing it.
+    ///
+    /// Collisions are sorted by distance, and their pairs by their first mark.
+    pub fn collisions(&self) -> Vec<Collision> {
+        self.pairs_by_distance().into_iter()
+            .filter(|(_, pairs)| pairs.len() > 1)
+            .map(|(distance, pairs)| Collision { distance, pairs })
+            .collect()
+    }
+
+    /// Return the number of pairs of marks that repeat the distance of another pair. Golomb rulers have none.
+    pub fn collision_count(&self) -> usize {
+        DistanceSet::from(self).collision_count()
+    }
+
+    /// Return the smallest set of marks to delete so that the remaining marks have the golomb property.
+    ///
+    /// Deleting 0 leaves a golomb ruler that starts at the next mark.
+    pub fn minimal_repair(&self) -> Vec<GInt> {
+
+        let mut distances = DistanceSet::from(self);
+        let mut removed = vec![];
+
+        // Iterative deepening, so the first repair we find is as small as possible
+        for budget in 0..=self.order() {
+            if repair(&mut distances, budget, &mut removed) {
+                break
             }
         }
 
+        removed.sort();
+        removed
+    }
+
+    /// Return the marks as a set
+    pub fn as_set(&self) -> HashSet<GInt> {
+        let mut out = HashSet::<GInt>::new();
+
+        for m in &self.marks {
+            out.insert(*m);
+        }
+
+        out
+    }
+
+
+    /// Used in enumeration algorithms. Check if the first order of distances are unique.
+    ///
+    /// Consider the following array
+    ///
+    /// `[1, 3, 4]`
+    ///
+    /// 0th order: `[1, 3, 4]`
+    /// 1st order: `[2, 3]`
+    /// 2nd order: `[1]`
+    ///
+    /// The idea is to prune our enumeration tree based on a O(2n) or O(n) check; n the number of marks on our ruler
+    /// We can later iterate over the returned rulers to actually filter them.
+    pub fn is_golomb_ruler_order_1(&self) -> bool {
+        // start with the last element of our ruler and subtract every other element
+        let set = self.as_set();
+        let base = self.length(); // 0 is implied, our first element
+
+        // The rulers [0] and [0, 1] have a single distance at most
+        let Some((_, inner)) = self.marks.split_last() else { return true };
+
+        for m in inner {
+            let d = dist(*m, base);
+            if set.contains(&d) {
+                return false;
+            }
+        }
         true
     }
 
-}
+    /// Return the distance between every pair of marks, 0 included, sorted by their first and then second mark.
+    pub fn distances(&self) -> Vec<Distance> {
+
+        let marks = self.to_list();
+        let mut out: Vec<Distance> = Vec::new();
+
+        for (idx, lhs) in marks.iter().enumerate() {
+            for rhs in &marks[idx + 1..] {
+                out.push(Distance {
+                    lhs: *lhs,
+                    rhs: *rhs,
+                    dist: dist(*lhs, *rhs)
+                });
+            }
+        }
+
+        out
+    }
+
+    /// Return every distance along with the pairs of marks producing it, sorted by first mark.
+    ///
+    /// Every distance of a golomb ruler has a single pair.
+    pub fn pairs_by_distance(&self) -> BTreeMap<GInt, Vec<(GInt, GInt)>> {
+        let mut pairs: BTreeMap<GInt, Vec<(GInt, GInt)>> = BTreeMap::new();
+        for d in self.distances() {
+            pairs.entry(d.dist).or_default().push((d.lhs, d.rhs));
+        }
+        pairs
+    }
+
+    /// Return the difference triangle as a multi-line string, each row centered below the row above.
+    ///
+    /// ```text
+    ///    1   3   5   2
+    ///      4   8   7
+    ///        9  10
+    ///         11
+    /// ```
+    pub fn format_triangle(&self) -> String {
+
+        let triangle = self.difference_triangle();
+        // An even cell width centers every row halfway between the cells above
+        let width = (self.length().to_string().len() + 2) / 2 * 2;
+
+        triangle.iter().enumerate().map(|(row, distances)| {
+            let indent = " ".repeat(row * width / 2);
+            let cells: String = distances.iter().map(|d| format!("{:>width$}", d, width = width)).collect();
+            format!("{}{}", indent, cells)
+        }).collect::<Vec<_>>().join("\n")
+    }
 
+    /// Return the id of this ruler, failing when it doesn't fit in a `usize`.
+    pub fn to_id(&self) -> Result<usize> {
+
+        // [0]
+        if self.marks.is_empty() {
+            Ok(0)
+        } else if self.length() == 1 {
+        // [0, 1]
+            Ok(1)
+        } else if self.length() > usize::BITS as GInt {
+            Err(OgrError::Overflow(format!("The id of a ruler of length {}", self.length())))
+        } else {
+            let mut val = 1 << (self.length() - 1);
+            // println!("Starting value: {}", val);
+            let state = self.to_state();
+            self.marks[0..self.marks.len() - 1].iter().for_each(|m| if state[(*m - 1) as usize] { val += 1 << (m - 1)} );
+
+            Ok(val)
+        }
+    }
+
+
+    pub fn to_state(&self) -> Vec<bool> {
+
+        let l = self.length();
+
+        if self.order() == 1 || self.length() == 1 {
+            return vec![]
+        }
+
+        // Initialize with all false, dropping the 0 and length
+        let mut state = vec![false; (l - 1) as usize];
+
+        // Drop the 0
+        for m in &self.marks[0..self.marks.len() - 1] {
+            state[*m as usize - 1] = true;
+        }
+
+        state
+    }
+
+}
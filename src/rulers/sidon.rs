@@ -0,0 +1,216 @@
+//! Greedy construction of Sidon sequences, whose marks form golomb rulers at every step.
+//!
+//! Starting from a set of marks, every step appends a new mark larger than the previous ones that keeps
+//! every distance different. Always picking the smallest such mark from `[1]` gives the Mian–Chowla sequence
+//! `1, 2, 4, 8, 13, 21, 31, ...`.
+
+// `#[new]` constructors expand to non-local impls with pyo3 0.20
+#![cfg_attr(feature = "python", allow(non_local_definitions))]
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+use crate::GInt;
+use crate::error::{OgrError, Result};
+use super::{DistanceSet, ExtensionError};
+use super::rng::SplitMix64;
+
+/// How a [`SidonSequence`] picks its next mark among the valid ones
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepRule {
+    /// The smallest valid mark
+    Smallest,
+    /// The largest valid mark at most `cap` past the last mark, or the smallest valid mark further away if there is none
+    LargestGap(GInt),
+    /// A random valid mark at most `window` past the last mark, or the smallest valid mark further away if there is none
+    Random { window: GInt, seed: u64 },
+}
+
+impl StepRule {
+
+    /// The name and value of the parameter bounding how far past the last mark we look, if any
+    fn width(&self) -> Option<(&'static str, GInt)> {
+        match *self {
+            StepRule::Smallest => None,
+            StepRule::LargestGap(cap) => Some(("cap", cap)),
+            StepRule::Random { window, .. } => Some(("window", window)),
+        }
+    }
+}
+
+/// Infinite lazy iterator over a greedy Sidon sequence.
+///
+/// The marks of the starting set are yielded first, in increasing order. Marks are never larger than `max_mark`,
+/// e.g. `u32::MAX as GInt` when marks are stored as `u32`: the rules only pick among the marks up to `max_mark`,
+/// and the sequence ends once none of them is valid.
+#[derive(Debug)]
+#[cfg_attr(feature = "python", pyclass)]
+pub struct SidonSequence {
+    distances: DistanceSet,
+    /// Marks of the starting set that haven't been yielded yet, in decreasing order
+    pending: Vec<GInt>,
+    rule: StepRule,
+    max_mark: GInt,
+    rng: SplitMix64,
+    done: bool,
+}
+
+impl SidonSequence {
+
+    /// Start a sequence from the marks `marks`, which must have the golomb property. An empty set starts at 1.
+    ///
+    /// The cap of [`StepRule::LargestGap`] and the window of [`StepRule::Random`] must be positive,
+    /// and the starting marks at most `max_mark`.
+    pub fn new(marks: &[GInt], rule: StepRule, max_mark: Option<GInt>) -> Result<Self> {
+
+        let marks = if marks.is_empty() { &[1] } else { marks };
+        let distances = DistanceSet::from_marks(marks);
+
+        if !distances.is_golomb() || distances.marks().len() != marks.len() {
+            return Err(ExtensionError::NotGolomb.into())
+        }
+
+        let max_mark = max_mark.unwrap_or(GInt::MAX);
+        if distances.marks().last().is_some_and(|m| *m > max_mark) {
+            return Err(OgrError::InvalidMarks {
+                marks: marks.to_vec(),
+                reason: format!("the starting marks must be at most the largest mark {}", max_mark),
+            })
+        }
+
+        if let Some((name, width)) = rule.width().filter(|(_, width)| *width <= 0) {
+            return Err(OgrError::InvalidArgument {
+                name: name.to_string(),
+                reason: format!("the {:?} rule needs a positive {}, got {}", rule, name, width),
+            })
+        }
+
+        let seed = match rule {
+            StepRule::Random { seed, .. } => seed,
+            _ => 0,
+        };
+
+        Ok(SidonSequence {
+            pending: distances.marks().iter().rev().copied().collect(),
+            distances,
+            rule,
+            max_mark,
+            rng: SplitMix64::new(seed),
+            done: false,
+        })
+    }
+
+    /// The Mian–Chowla sequence `1, 2, 4, 8, 13, 21, 31, ...`
+    pub fn mian_chowla() -> Self {
+        SidonSequence::new(&[1], StepRule::Smallest, None).unwrap()
+    }
+
+    /// Check if `p` can be added to our marks
+    fn is_valid(&mut self, p: GInt) -> bool {
+        if !self.distances.add_mark(p) {
+            return false
+        }
+        let valid = self.distances.is_golomb();
+        self.distances.remove_mark(p);
+        valid
+    }
+
+    /// The smallest valid mark in `[from, max_mark]`. Marks far enough past our last mark are always valid.
+    fn smallest_from(&mut self, from: GInt) -> Option<GInt> {
+        (from..=self.max_mark).find(|p| self.is_valid(*p))
+    }
+
+    /// Pick the next mark according to our rule, or None if no mark up to `max_mark` is valid
+    fn step(&mut self) -> Option<GInt> {
+
+        let last = *self.distances.marks().last().unwrap();
+        if last >= self.max_mark {
+            return None
+        }
+
+        // Marks at most `width` past the last mark, and at most `max_mark`
+        let max_mark = self.max_mark;
+        let window = |width: GInt| last + 1..=GInt::min(last.saturating_add(width), max_mark);
+        let beyond = |width: GInt| last.saturating_add(width).saturating_add(1);
+
+        match self.rule {
+            StepRule::Smallest => self.smallest_from(last + 1),
+            StepRule::LargestGap(cap) => match window(cap).rev().find(|p| self.is_valid(*p)) {
+                Some(p) => Some(p),
+                None => self.smallest_from(beyond(cap)),
+            },
+            StepRule::Random { window: width, .. } => {
+                let valid: Vec<GInt> = window(width).filter(|p| self.is_valid(*p)).collect();
+                match valid.len() {
+                    0 => self.smallest_from(beyond(width)),
+                    n => Some(valid[self.rng.below(n)]),
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for SidonSequence {
+    type Item = GInt;
+
+    fn next(&mut self) -> Option<Self::Item> {
+
+        if self.done {
+            return None
+        }
+
+        if let Some(p) = self.pending.pop() {
+            return Some(p)
+        }
+
+        match self.step() {
+            Some(p) => {
+                self.distances.add_mark(p);
+                Some(p)
+            },
+            None => {
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl SidonSequence {
+
+    /// Create a greedy Sidon sequence starting from `marks`, the Mian–Chowla sequence by default.
+    ///
+    /// `rule` is one of `"smallest"`, `"largest_gap"` or `"random"`. The last two pick a mark at most `cap`
+    /// past the last one, and `"random"` is reproducible for a given `seed`.
+    #[new]
+    #[pyo3(signature = (marks=Vec::new(), rule="smallest", cap=None, seed=0, max_mark=None))]
+    fn py_new(marks: Vec<GInt>, rule: &str, cap: Option<GInt>, seed: u64, max_mark: Option<GInt>) -> PyResult<Self> {
+
+        let cap = || cap.ok_or_else(|| OgrError::InvalidArgument {
+            name: "cap".to_string(),
+            reason: format!("the '{}' rule needs a cap", rule),
+        });
+
+        let rule = match rule {
+            "smallest" => StepRule::Smallest,
+            "largest_gap" => StepRule::LargestGap(cap()?),
+            "random" => StepRule::Random { window: cap()?, seed },
+            _ => return Err(OgrError::InvalidArgument {
+                name: "step rule".to_string(),
+                reason: format!("unknown rule '{}', expected one of 'smallest', 'largest_gap' or 'random'", rule),
+            }.into())
+        };
+
+        Ok(SidonSequence::new(&marks, rule, max_mark)?)
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<'_, Self>) -> Option<GInt> {
+        slf.next()
+    }
+}
@@ -0,0 +1,246 @@
+//! Reconstruct rulers from the multiset of their pairwise distances.
+//!
+//! This is the turnpike (or partial digest) problem, the inverse of [`GolombRuler::distances`]. Its cyclic
+//! variant, the beltway problem, reconstructs marks on a circle, as for modular golomb rulers.
+//!
+//! Both are solved with backtracking: in the turnpike problem, the largest distance that isn't accounted for
+//! yet must be between a new mark and one of the two ends of the ruler.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+use crate::{GInt, dist};
+use crate::error::{OgrError, Result};
+use super::Ruler;
+
+/// Return the number of marks whose `pairs` pairwise distances are given, if any.
+fn order_from_pairs(pairs: usize, cyclic: bool) -> Option<usize> {
+    // Linear distances count each pair once, cyclic distances twice
+    if cyclic && !pairs.is_multiple_of(2) {
+        return None
+    }
+    let pairs = if cyclic { pairs / 2 } else { pairs };
+    (1..).map(|n: usize| (n, n * (n - 1) / 2)).find(|(_, p)| *p >= pairs).filter(|(_, p)| *p == pairs).map(|(n, _)| n)
+}
+
+/// Count how many times each distance appears. Fails for distances that are not positive.
+fn count_distances(distances: &[GInt]) -> Result<BTreeMap<GInt, usize>> {
+
+    let mut counts = BTreeMap::new();
+
+    for d in distances {
+        if *d <= 0 {
+            return Err(OgrError::InvalidMarks {
+                marks: distances.to_vec(),
+                reason: format!("invalid distances, {} is not positive", d),
+            })
+        }
+        *counts.entry(*d).or_insert(0) += 1;
+    }
+
+    Ok(counts)
+}
+
+/// Consume one occurrence of the distance `d`, if there is any left
+fn take(counts: &mut BTreeMap<GInt, usize>, d: GInt) -> bool {
+    match counts.get_mut(&d) {
+        Some(1) => { counts.remove(&d); true },
+        Some(c) => { *c -= 1; true },
+        None => false,
+    }
+}
+
+/// Give back one occurrence of the distance `d`
+fn give(counts: &mut BTreeMap<GInt, usize>, d: GInt) {
+    *counts.entry(d).or_insert(0) += 1;
+}
+
+/// State of the turnpike backtracking
+struct Turnpike {
+    /// Distances that aren't accounted for yet, without zeros
+    counts: BTreeMap<GInt, usize>,
+    remaining: usize,
+    marks: Vec<GInt>,
+    width: GInt,
+    solutions: BTreeSet<Ruler>,
+    /// Stop at the first solution
+    first_only: bool,
+}
+
+impl Turnpike {
+
+    /// Try to add `p` to our marks, consuming its distances to every other mark
+    fn place(&mut self, p: GInt) -> bool {
+
+        if self.marks.contains(&p) {
+            return false
+        }
+
+        for (idx, m) in self.marks.iter().enumerate() {
+            if !take(&mut self.counts, dist(p, *m)) {
+                // Give back the distances we took so far
+                self.marks[..idx].iter().for_each(|m| give(&mut self.counts, dist(p, *m)));
+                return false
+            }
+        }
+
+        self.remaining -= self.marks.len();
+        self.marks.push(p);
+        true
+    }
+
+    /// Remove our last mark, giving back its distances
+    fn unplace(&mut self) {
+        let p = self.marks.pop().unwrap();
+        self.marks.iter().for_each(|m| give(&mut self.counts, dist(p, *m)));
+        self.remaining += self.marks.len();
+    }
+
+    /// Explore every way to place the largest remaining distance. Returns true once we should stop.
+    fn search(&mut self, first: bool) -> bool {
+
+        if self.remaining == 0 {
+            let mut marks = self.marks.clone();
+            marks.sort();
+            self.solutions.insert(Ruler::new(&marks[1..]).canonical());
+            return self.first_only
+        }
+
+        let y = *self.counts.keys().next_back().unwrap();
+
+        // The very first mark can be placed on the left by symmetry
+        let candidates = if first { vec![y] } else { vec![y, self.width - y] };
+
+        for p in candidates {
+            if self.place(p) {
+                if self.search(false) {
+                    return true
+                }
+                self.unplace();
+            }
+        }
+
+        false
+    }
+}
+
+/// Return every ruler, up to mirror symmetry, whose pairwise distances are exactly the multiset `distances`.
+///
+/// When the distances are all different, they come from a golomb ruler. By Piccard's theorem, such a ruler is unique
+/// up to reflection, so we stop at the first solution. The only exceptions have 6 marks, like `[0, 1, 4, 10, 12, 17]`
+/// and `[0, 1, 8, 11, 13, 17]`, and are searched exhaustively.
+///
+/// Fails for distances that are not positive.
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn reconstruct_rulers(distances: Vec<GInt>) -> Result<Vec<Ruler>> {
+
+    let mut counts = count_distances(&distances)?;
+
+    let order = match order_from_pairs(distances.len(), false) {
+        Some(order) => order,
+        None => return Ok(vec![]),
+    };
+
+    if distances.is_empty() {
+        return Ok(vec![Ruler::default()])
+    }
+
+    // The largest distance is between both ends of the ruler
+    let width = *counts.keys().next_back().unwrap();
+    take(&mut counts, width);
+
+    let golomb = counts.values().all(|c| *c <= 1);
+    let mut turnpike = Turnpike {
+        counts,
+        remaining: distances.len() - 1,
+        marks: vec![0, width],
+        width,
+        solutions: BTreeSet::new(),
+        first_only: golomb && order != 6,
+    };
+
+    turnpike.search(true);
+    Ok(turnpike.solutions.into_iter().collect())
+}
+
+/// Return the smallest rotation or reflection of the marks `marks` on a circle of circumference `circumference`
+fn canonical_cycle(marks: &[GInt], circumference: GInt) -> Vec<GInt> {
+
+    let reflected: Vec<GInt> = marks.iter().map(|m| (circumference - m) % circumference).collect();
+
+    [marks, &reflected].into_iter().flat_map(|marks| marks.iter().map(move |r| {
+        let mut rotated: Vec<GInt> = marks.iter().map(|m| (m - r).rem_euclid(circumference)).collect();
+        rotated.sort();
+        rotated
+    })).min().unwrap_or_default()
+}
+
+/// Place the remaining marks after `marks` on a circle, consuming their cyclic distances from `counts`
+fn beltway(marks: &mut Vec<GInt>, order: usize, counts: &mut BTreeMap<GInt, usize>, circumference: GInt, solutions: &mut BTreeSet<Vec<GInt>>) {
+
+    if marks.len() == order {
+        solutions.insert(canonical_cycle(marks, circumference));
+        return
+    }
+
+    let last = marks[marks.len() - 1];
+
+    // The distance from 0 to a new mark is the mark itself, so only remaining distances are candidates
+    let candidates: Vec<GInt> = counts.range(last + 1..circumference).map(|(d, _)| *d).collect();
+
+    for p in candidates {
+
+        // Clockwise and counterclockwise distances to every mark
+        let mut taken = vec![];
+        let fits = marks.iter().all(|m| [p - m, circumference - (p - m)].into_iter().all(|d| {
+            let ok = take(counts, d);
+            if ok {
+                taken.push(d);
+            }
+            ok
+        }));
+
+        if fits {
+            marks.push(p);
+            beltway(marks, order, counts, circumference, solutions);
+            marks.pop();
+        }
+
+        taken.into_iter().for_each(|d| give(counts, d));
+    }
+}
+
+/// Return every set of marks on a circle of circumference `circumference`, up to rotation and reflection,
+/// whose cyclic distances are exactly the multiset `distances`.
+///
+/// `distances` holds both arcs between every pair of marks, `d` and `circumference - d`. Solutions are
+/// rotated so that they contain 0.
+///
+/// Fails for distances that are not positive and for a circumference that is not positive.
+#[cfg_attr(feature = "python", pyfunction)]
+pub fn reconstruct_beltway(distances: Vec<GInt>, circumference: GInt) -> Result<Vec<Vec<GInt>>> {
+
+    if circumference <= 0 {
+        return Err(OgrError::InvalidArgument {
+            name: "circumference".to_string(),
+            reason: format!("the circumference of a circle must be positive, got {}", circumference),
+        })
+    }
+
+    let mut counts = count_distances(&distances)?;
+
+    let order = match order_from_pairs(distances.len(), true) {
+        Some(order) => order,
+        None => return Ok(vec![]),
+    };
+
+    if distances.iter().any(|d| *d >= circumference) {
+        return Ok(vec![])
+    }
+
+    let mut solutions = BTreeSet::new();
+    beltway(&mut vec![0], order, &mut counts, circumference, &mut solutions);
+    Ok(solutions.into_iter().collect())
+}